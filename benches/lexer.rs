@@ -0,0 +1,36 @@
+#![feature(test)]
+extern crate test;
+
+use lang::tokenizer::Tokenizer;
+use test::Bencher;
+
+/// Generated program of roughly `lines` lines, resembling the sources
+/// produced by code generators: many distinct names, long expressions.
+fn synthetic_source(lines: usize) -> String {
+    let mut source = String::from("{\n");
+    for i in 0..lines {
+        source.push_str(&format!(
+            "    let value_{} := {} * (input_{} + 42) / scale - offset_{};\n",
+            i % 1000,
+            i,
+            i % 97,
+            i % 13,
+        ));
+    }
+    source.push_str("    return value_0;\n}\n");
+    source
+}
+
+#[bench]
+fn bench_tokenize_large_file(b: &mut Bencher) {
+    let source = synthetic_source(100_000);
+    b.bytes = source.len() as u64;
+    b.iter(|| Tokenizer::from(source.as_str()).count());
+}
+
+#[bench]
+fn bench_tokenize_unicode_identifiers(b: &mut Bencher) {
+    let source = synthetic_source(100_000).replace("value", "größe").replace("input", "π");
+    b.bytes = source.len() as u64;
+    b.iter(|| Tokenizer::from(source.as_str()).count());
+}
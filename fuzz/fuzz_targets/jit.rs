@@ -0,0 +1,150 @@
+//! Builds random well-typed programs and runs them with the JIT and with
+//! the interpreter, checking that code generation succeeds and that both
+//! give the same value or fail the same runtime check. The JIT runs the
+//! folded program, so this checks constant folding too.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use inkwell::context::Context;
+use lang::codegen::CodeGen;
+use lang::error::ErrorKind;
+use lang::interp::Interpreter;
+use lang::lint::{Level, Lint, LintLevels};
+use lang::typeck::Type;
+use lang::{fold, frontend, runtime};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Program {
+    /// Whether the value of the program is an `i64` rather than an `f64`.
+    integer: bool,
+    value: Node,
+}
+
+/// An expression, which `Writer` writes with the type it is expected to
+/// have.
+#[derive(Arbitrary, Debug)]
+enum Node {
+    Literal(u16),
+    /// One of the variables in scope with the expected type, if any.
+    Variable(u8),
+    Binary(Operator, Box<Node>, Box<Node>),
+    /// A value of the other type, converted.
+    Cast(Box<Node>),
+    /// A block of `let` statements, whose value is an `i64` or not and has
+    /// a declared type or not, then its value.
+    Block(Vec<(bool, bool, Node)>, Box<Node>),
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Nodes deeper than this are written as literals, to keep programs small.
+const MAX_DEPTH: usize = 24;
+
+struct Writer {
+    out: String,
+    /// Variables in scope, innermost last.
+    variables: Vec<(String, Type)>,
+    /// How many variables were declared, to name the next one.
+    declared: usize,
+}
+
+impl Writer {
+    fn node(&mut self, node: &Node, type_: Type, depth: usize) {
+        match node {
+            _ if depth > MAX_DEPTH => self.literal(1, type_),
+            Node::Literal(x) => self.literal(*x, type_),
+            Node::Variable(i) => {
+                let candidates = self.variables.iter().filter(|(_, t)| *t == type_).collect::<Vec<_>>();
+                match candidates.get(*i as usize % candidates.len().max(1)).map(|(name, _)| name.clone()) {
+                    Some(name) => self.out.push_str(&name),
+                    None => self.literal(*i as u16, type_),
+                }
+            },
+            Node::Binary(operator, a, b) => {
+                self.operand(a, type_, depth);
+                self.out.push_str(match operator {
+                    Operator::Add => " + ",
+                    Operator::Sub => " - ",
+                    Operator::Mul => " * ",
+                    Operator::Div => " / ",
+                });
+                self.operand(b, type_, depth);
+            },
+            Node::Cast(value) => {
+                let other = match type_ {
+                    Type::F64 => Type::I64,
+                    Type::I64 => Type::F64,
+                };
+                self.operand(value, other, depth);
+                self.out.push_str(&format!(" as {}", type_));
+            },
+            Node::Block(lines, value) => {
+                let scope = self.variables.len();
+                self.out.push_str("{ ");
+                for (integer, declared, value) in lines {
+                    let line_type = if *integer { Type::I64 } else { Type::F64 };
+                    let name = format!("v{}", self.declared);
+                    self.declared += 1;
+                    match declared {
+                        true => self.out.push_str(&format!("let {}: {} = ", name, line_type)),
+                        false => self.out.push_str(&format!("let {} := ", name)),
+                    }
+                    self.node(value, line_type, depth + 1);
+                    self.out.push_str("; ");
+                    self.variables.push((name, line_type));
+                }
+                self.node(value, type_, depth + 1);
+                self.out.push_str(" }");
+                self.variables.truncate(scope);
+            },
+        }
+    }
+
+    fn operand(&mut self, node: &Node, type_: Type, depth: usize) {
+        self.out.push('(');
+        self.node(node, type_, depth + 1);
+        self.out.push(')');
+    }
+
+    fn literal(&mut self, x: u16, type_: Type) {
+        match type_ {
+            Type::F64 => self.out.push_str(&x.to_string()),
+            Type::I64 => self.out.push_str(&format!("{} as i64", x)),
+        }
+    }
+}
+
+fuzz_target!(|program: Program| {
+    let mut writer = Writer { out: String::new(), variables: Vec::new(), declared: 0 };
+    let type_ = if program.integer { Type::I64 } else { Type::F64 };
+    writer.node(&program.value, type_, 0);
+    let text = writer.out;
+
+    // Operations which always fail are meant to fail at runtime here.
+    let mut levels = LintLevels::default();
+    levels.set(Lint::UnconditionalFault, Level::Allow);
+    let checked = frontend::check(&text, &levels);
+    assert!(!checked.has_errors(), "{}: {:?}", text, checked.diagnostics().collect::<Vec<_>>());
+
+    let expected = Interpreter::new().run(&checked.program);
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context).expect("the JIT is available").with_recoverable_traps();
+    let main = codegen.compile_main(&fold::fold(&checked.program)).unwrap_or_else(|e| panic!("{}: {:?}", text, e));
+    runtime::take_trap();
+    let value = unsafe { main.call(0, 0) };
+    match (expected, runtime::take_trap()) {
+        (Ok(expected), None) => {
+            assert!(expected == value || (expected.is_nan() && value.is_nan()), "{}: {} != {}", text, expected, value);
+        },
+        (Err(error), Some(trap)) if error.kind == ErrorKind::Runtime => assert_eq!(error.message, trap, "{}", text),
+        (expected, trap) => panic!("{}: the interpreter gives {:?} but the JIT {:?}", text, expected, trap),
+    }
+});
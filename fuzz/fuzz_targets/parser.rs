@@ -0,0 +1,26 @@
+//! Runs the front-end on arbitrary input, as a program and as an entry of
+//! the REPL, checking that it reports errors instead of panicking and that
+//! they can be shown.
+
+#![no_main]
+
+use std::collections::HashMap;
+
+use lang::diagnostic::Diagnostic;
+use lang::frontend;
+use lang::lint::LintLevels;
+use lang::source::SourceFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = SourceFile::new("fuzz.x", String::from_utf8_lossy(data));
+    let levels = LintLevels::default();
+    let checked = frontend::check(&source.text, &levels);
+    for diagnostic in checked.diagnostics() {
+        diagnostic.render(&source);
+    }
+    let entry = frontend::check_entry(&source.text, &levels, &HashMap::new());
+    for error in &entry.errors {
+        Diagnostic::from(error).render(&source);
+    }
+});
@@ -0,0 +1,22 @@
+//! Tokenizes arbitrary input, checking that every token, valid or not, has
+//! a span within the input and after the previous one.
+
+#![no_main]
+
+use lang::tokenizer::Tokenizer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let mut tokenizer = Tokenizer::from(text.as_ref());
+    let mut end = 0;
+    while let Some((_, span)) = tokenizer.next_raw() {
+        assert!(end <= span.start && span.start < span.end && span.end <= text.len(), "{:?} after {}", span, end);
+        assert!(text.is_char_boundary(span.start) && text.is_char_boundary(span.end));
+        end = span.end;
+    }
+
+    // Skipping the invalid tokens records them instead.
+    let tokenizer = Tokenizer::from(text.as_ref());
+    for _ in tokenizer {}
+});
@@ -0,0 +1,69 @@
+use crate::error::CompileError;
+use crate::parser::{Parsable, Parser};
+use crate::span::Span;
+use crate::tokenizer::Token;
+
+/// `#[name(arg, ...)]` written before a line, or `#![name(arg, ...)]` at
+/// the start of a block or file, applying to what contains it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: String,
+    /// Arguments with their spans, e.g. the lints of `#[allow(...)]`.
+    pub args: Vec<(String, Span)>,
+    pub inner: bool,
+    pub span: Span,
+}
+
+impl Parsable for Attribute {
+    fn parse(parser: &mut Parser) -> Result<Self, CompileError> {
+        parser.tokens.expect_operator_of('#')?;
+        let start = parser.tokens.span();
+        let inner = parser.tokens.peek() == Some(&Token::Operator('!'));
+        if inner {
+            parser.tokens.next();
+        }
+        parser.tokens.expect_operator_of('[')?;
+        let name = match parser.tokens.peek() {
+            // Keywords name attributes too, e.g. `#[test]`.
+            Some(&Token::Keyword(keyword)) => {
+                parser.tokens.next();
+                keyword.as_str().to_string()
+            },
+            _ => parser.tokens.expect_symbol()?,
+        };
+        let mut args = Vec::new();
+        if parser.tokens.peek() == Some(&Token::Operator('(')) {
+            parser.tokens.next();
+            while parser.tokens.peek() != Some(&Token::Operator(')')) {
+                args.push((parser.tokens.expect_symbol()?, parser.tokens.span()));
+                match parser.tokens.peek() {
+                    Some(Token::Operator(',')) => {
+                        parser.tokens.next();
+                    },
+                    _ => break,
+                }
+            }
+            parser.tokens.expect_operator_of(')')?;
+        }
+        parser.tokens.expect_operator_of(']')?;
+        Ok(Attribute { name, args, inner, span: start.to(parser.tokens.span()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let mut parser = Parser::from("#[allow(a, b)] #![test]");
+        let attribute = Attribute::parse(&mut parser).unwrap();
+        assert_eq!(attribute.name, "allow");
+        assert_eq!(attribute.args, vec![("a".to_string(), Span::new(8, 9)), ("b".to_string(), Span::new(11, 12))]);
+        assert!(!attribute.inner);
+        assert_eq!(attribute.span, Span::new(0, 14));
+        let attribute = Attribute::parse(&mut parser).unwrap();
+        assert_eq!(attribute.name, "test");
+        assert!(attribute.inner && attribute.args.is_empty());
+    }
+}
@@ -1,103 +1,356 @@
-use crate::tokenizer::Token;
-use crate::{expression::Expression, parser::Parsable};
+use crate::attribute::Attribute;
+use crate::code::ErrorCode;
+use crate::error::{Applicability, CompileError};
+use crate::span::Span;
+use crate::suggest;
+use crate::tokenizer::{Keyword, Token};
+use crate::expression::{Comparison, Expression};
+use crate::parser::Parsable;
 
 #[derive(Debug, Clone)]
 pub enum Line {
     Expression(Expression),
     LetStatement {
         name: String,
+        /// Span of the name.
+        span: Span,
         value: Expression,
-        type_: Option<String>,
+        /// The declared type with its span.
+        type_: Option<(String, Span)>,
     },
     ReturnStatement(Expression),
+    /// `assert(left <comparison> right)`, which stops the program when the
+    /// comparison does not hold.
+    Assert {
+        left: Expression,
+        comparison: Comparison,
+        right: Expression,
+        /// Span of the condition, from `left` to `right`.
+        span: Span,
+    },
+    /// A line which failed to parse; its error was recorded by the parser.
+    Error(Span),
+}
+
+/// Where a line of a block is and the attributes written before it.
+#[derive(Debug, Clone, Default)]
+pub struct LineInfo {
+    pub span: Span,
+    pub attributes: Vec<Attribute>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Block {
     pub lines: Vec<Line>,
+    /// One per line.
+    pub info: Vec<LineInfo>,
+    /// `#![...]` attributes at the start of the block.
+    pub attributes: Vec<Attribute>,
+    /// From `{` to `}`, or the whole input for an entry of the REPL.
+    pub span: Span,
 }
 
 impl Block {
     pub fn has_value(&self) -> bool {
-        self.lines.last().map_or(false, |line| match line {
-            Line::ReturnStatement(_) => true,
-            _ => false,
-        })
+        matches!(self.lines.last(), Some(Line::ReturnStatement(_)))
     }
 
-    fn parse_let(parser: &mut crate::parser::Parser) -> Result<Line, String> {
+    /// Whether some lines failed to parse, in which case the block is only
+    /// kept to report more errors.
+    pub fn has_errors(&self) -> bool {
+        self.lines.iter().any(|line| matches!(line, Line::Error(_)))
+    }
+
+    fn parse_let(parser: &mut crate::parser::Parser) -> Result<Line, CompileError> {
         use Token as T;
-        parser.tokens.next();
+        parser.tokens.expect_keyword(Keyword::Let)?;
         let name = parser.tokens.expect_symbol()?;
-        parser.tokens.expect_operator_of(':')?;
+        let span = parser.tokens.span();
+        match parser.tokens.next() {
+            Some(T::Operator(':')) => (),
+            Some(T::Operator('=')) => {
+                let error = CompileError::parse("Expected operator ':' but found '='", parser.tokens.span())
+                    .with_code(ErrorCode::LetWithoutColon);
+                return Err(error.with_suggestion(
+                    parser.tokens.span(),
+                    ":=",
+                    "infer the type of the variable",
+                    Applicability::MachineApplicable,
+                ));
+            },
+            token => return Err(parser.tokens.unexpected(token, "operator ':'")),
+        }
         let type_ = match parser.tokens.next() {
-            Some(Ok(T::Symbol(type_))) => Some(type_),
-            Some(Ok(T::Operator('='))) => None,
-            Some(Err(e)) => return Err(e),
-            _ => return Err(format!("Expected type or '=' after let {}:", name)),
+            Some(T::Symbol(type_)) => Some((parser.tokens.resolve(type_).to_string(), parser.tokens.span())),
+            Some(T::Operator('=')) => None,
+            Some(T::Keyword(k)) => return Err(parser.tokens.reserved_keyword_error(k)),
+            token => {
+                let error = parser.tokens.unexpected(token, "type or '='");
+                return Err(error.with_note(format!("in the declaration of '{}'", name)))
+            },
         };
         if type_.is_some() {
             parser.tokens.expect_operator_of('=')?;
         }
         let value = Expression::parse(parser)?;
-        Ok(Line::LetStatement { name, value, type_ })
+        Ok(Line::LetStatement { name, span, value, type_ })
     }
 
-    fn parse_return(parser: &mut crate::parser::Parser) -> Result<Line, String> {
-        parser.tokens.next();
+    fn parse_return(parser: &mut crate::parser::Parser) -> Result<Line, CompileError> {
+        parser.tokens.expect_keyword(Keyword::Return)?;
         let value = Expression::parse(parser)?;
         Ok(Line::ReturnStatement(value))
     }
+
+    fn parse_assert(parser: &mut crate::parser::Parser) -> Result<Line, CompileError> {
+        parser.tokens.expect_keyword(Keyword::Assert)?;
+        parser.tokens.expect_operator_of('(')?;
+        let open = parser.tokens.span();
+        let start = parser.tokens.peek_span();
+        let left = Expression::parse(parser)?;
+        let comparison = Comparison::parse(parser)?;
+        let right = Expression::parse(parser)?;
+        let span = start.to(parser.tokens.span());
+        parser.tokens.expect_operator_of(')').map_err(|e| e.with_label(open, "unclosed parenthesis"))?;
+        Ok(Line::Assert { left, comparison, right, span })
+    }
+
+    fn parse_line(parser: &mut crate::parser::Parser) -> Result<Line, CompileError> {
+        match parser.tokens.peek() {
+            Some(Token::Keyword(Keyword::Let)) => Block::parse_let(parser),
+            Some(Token::Keyword(Keyword::Return)) => Block::parse_return(parser),
+            Some(Token::Keyword(Keyword::Assert)) => Block::parse_assert(parser),
+            _ => Ok(Line::Expression(Expression::parse(parser)?)),
+        }
+    }
+
+    /// The keyword a line which is only a name followed by something else
+    /// than `;` probably misspells, e.g. `retrun` in `retrun 1;`.
+    fn misspelled_keyword(line: &Line) -> Option<(Keyword, Span)> {
+        match line {
+            Line::Expression(Expression::Variable(name, span)) => match suggest::keyword(name) {
+                Some(keyword @ (Keyword::Let | Keyword::Return)) => Some((keyword, *span)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Error for a line which ends at `end` and is followed by the next
+    /// token instead of `;`.
+    fn missing_semicolon(parser: &mut crate::parser::Parser, line: &Line, end: usize) -> CompileError {
+        let error = parser.tokens.unexpected_peeked("';'").with_code(ErrorCode::MissingSemicolon);
+        if let Some((keyword, span)) = Block::misspelled_keyword(line) {
+            let message = "there is a keyword with a similar name";
+            return error.with_suggestion(span, keyword.as_str(), message, Applicability::MaybeIncorrect);
+        }
+        // Adding `;` is only right when another line starts where it is
+        // missing.
+        let applicability = match Block::starts_line(parser.tokens.peek()) {
+            true => Applicability::MachineApplicable,
+            false => Applicability::MaybeIncorrect,
+        };
+        error.with_suggestion(Span::new(end, end), ";", "add the missing semicolon", applicability)
+    }
+
+    /// Whether a line can start with `token`.
+    fn starts_line(token: Option<&Token>) -> bool {
+        matches!(
+            token,
+            Some(
+                Token::Number
+                    | Token::Symbol(_)
+                    | Token::Keyword(Keyword::Let | Keyword::Return | Keyword::Assert)
+                    | Token::Operator('{' | '(')
+            )
+        )
+    }
+
+    /// Skips the rest of a line which failed to parse, up to its `;`.
+    fn recover(parser: &mut crate::parser::Parser, start: Span) {
+        parser.synchronize();
+        if parser.tokens.peek_span() == start {
+            // Always make progress, even when the line failed on the token
+            // parsing resumes from.
+            parser.tokens.next();
+        }
+        if parser.tokens.peek() == Some(&Token::Operator(';')) {
+            parser.tokens.next();
+        }
+    }
 }
 
 impl Parsable for Block {
-    fn parse(parser: &mut crate::parser::Parser) -> Result<Self, String> {
-        use Token as T;
-        let mut lines = Vec::new();
+    /// Parses a block, recovering from errors in its lines: they are recorded
+    /// in the parser and replaced by `Line::Error`, and parsing goes on at the
+    /// next line.
+    fn parse(parser: &mut crate::parser::Parser) -> Result<Self, CompileError> {
         parser.tokens.expect_operator_of('{')?;
-        while let Some(token) = parser.tokens.peek() {
-            let token = (token.as_ref())?;
-            lines.push(if *token == T::Symbol("let".to_string()) {
-                Block::parse_let(parser)?
-            } else if *token == T::Symbol("return".to_string()) {
-                Block::parse_return(parser)?
-            } else if *token == T::Operator('}') {
-                break;
-            } else {
-                let value = Expression::parse(parser)?;
-                Line::Expression(value)
-            });
-            match parser.tokens.next() {
-                Some(Err(e)) => return Err(e),
-                Some(Ok(T::Operator(';'))) => (),
-                Some(Ok(T::Operator('}'))) => match lines.last() {
-                    Some(Line::Expression(expr)) => {
-                        *lines.last_mut().unwrap() = Line::ReturnStatement(expr.clone());
-                        break
-                    },
-                    _ => return Err("Expected expression before '}' or ';' operator".to_string()),
+        let open = parser.tokens.span();
+        Block::parse_lines(parser, Some(open))
+    }
+}
+
+impl Block {
+    /// Parses the whole input as the lines of a block without braces, e.g.
+    /// an entry of the REPL. Errors are recorded in the parser.
+    pub fn parse_entry(parser: &mut crate::parser::Parser) -> Block {
+        Block::parse_lines(parser, None).expect("only unclosed blocks fail to parse")
+    }
+
+    /// Parses lines up to the `}` closing the block opened at `open`, or up
+    /// to the end of the input without `open`.
+    fn parse_lines(parser: &mut crate::parser::Parser, open: Option<Span>) -> Result<Self, CompileError> {
+        use Token as T;
+        let open_span = open.unwrap_or_default();
+        let mut block = Block { lines: Vec::new(), info: Vec::new(), attributes: Vec::new(), span: open_span };
+        let mut attributes: Vec<Attribute> = Vec::new();
+        loop {
+            match (parser.tokens.peek(), open) {
+                (None, None) => {
+                    if let Some(attribute) = attributes.last() {
+                        parser.record(CompileError::parse("Expected a line after attribute", attribute.span)
+                            .with_code(ErrorCode::MisplacedAttribute));
+                    }
+                    block.span = open_span.to(parser.tokens.end_span());
+                    break;
+                },
+                (None, Some(open)) => {
+                    let end = parser.tokens.end_span();
+                    let error = parser
+                        .tokens
+                        .unexpected(None, "'}'")
+                        .with_code(ErrorCode::UnclosedDelimiter)
+                        .with_label(open, "unclosed block");
+                    return Err(error.with_suggestion(end, "}", "close the block", Applicability::MaybeIncorrect));
+                },
+                (Some(T::Operator('}')), None) => {
+                    let token = parser.tokens.next();
+                    parser.record(parser.tokens.unexpected(token, "line"));
+                    continue;
+                },
+                (Some(T::Operator('}')), Some(_)) => {
+                    parser.tokens.next();
+                    if let Some(attribute) = attributes.last() {
+                        let error = CompileError::parse("Expected a line after attribute", attribute.span);
+                        parser.record(error.with_code(ErrorCode::MisplacedAttribute));
+                    }
+                    block.span = open_span.to(parser.tokens.span());
+                    break;
                 },
-                Some(Ok(t)) => return Err(format!("Expected ';' but found '{}'", t)),
-                None => return Err("Expected ';' but found end of input".to_string()),
+                (Some(T::Operator('#')), _) => {
+                    let start = parser.tokens.peek_span();
+                    match Attribute::parse(parser) {
+                        Ok(attribute) if !attribute.inner => attributes.push(attribute),
+                        Ok(attribute) if block.lines.is_empty() && attributes.is_empty() => {
+                            block.attributes.push(attribute)
+                        },
+                        Ok(attribute) => parser.record(
+                            CompileError::parse("Inner attributes are only allowed at the start of a block or file", attribute.span)
+                                .with_code(ErrorCode::MisplacedAttribute)
+                                .with_help("use an outer attribute '#[...]' to apply it to the next line only"),
+                        ),
+                        Err(error) => {
+                            parser.record(error);
+                            Block::recover(parser, start);
+                        },
+                    }
+                    continue;
+                },
+                (Some(_), _) => (),
             }
-            if Some(&Ok(T::Operator('}'))) == parser.tokens.peek() {
-                parser.tokens.next();
-                break;
+            let start = parser.tokens.peek_span();
+            let line = match Block::parse_line(parser) {
+                Ok(line) => line,
+                Err(error) => {
+                    parser.record(error);
+                    Block::recover(parser, start);
+                    Line::Error(start.to(parser.tokens.span()))
+                },
+            };
+            let failed = matches!(line, Line::Error(_));
+            block.lines.push(line);
+            block.info.push(LineInfo {
+                span: start.to(parser.tokens.span()),
+                attributes: std::mem::take(&mut attributes),
+            });
+            if failed {
+                continue;
+            }
+            match parser.tokens.peek() {
+                Some(T::Operator(';')) => {
+                    parser.tokens.next();
+                },
+                Some(T::Operator('}')) if open.is_some() => {
+                    parser.tokens.next();
+                    block.span = open_span.to(parser.tokens.span());
+                    match block.lines.pop() {
+                        Some(Line::Expression(expr)) => block.lines.push(Line::ReturnStatement(expr)),
+                        // Asserts need no `;`, leaving the block without value.
+                        Some(line @ Line::Assert { .. }) => block.lines.push(line),
+                        line => {
+                            block.lines.extend(line);
+                            parser.record(
+                                CompileError::parse("Expected expression before '}' or ';' operator", parser.tokens.span())
+                                    .with_code(ErrorCode::UnexpectedToken),
+                            );
+                        },
+                    }
+                    break;
+                },
+                // The last line of an entry is its value.
+                None if open.is_none() => {
+                    block.span = open_span.to(parser.tokens.end_span());
+                    match block.lines.pop() {
+                        Some(Line::Expression(expr)) => block.lines.push(Line::ReturnStatement(expr)),
+                        line => block.lines.extend(line),
+                    }
+                    break;
+                },
+                // Reported as an unclosed block by the next iteration.
+                None => (),
+                _ => {
+                    let end = parser.tokens.span().end;
+                    let line = block.lines.last().expect("a line was just parsed");
+                    let error = Block::missing_semicolon(parser, line, end);
+                    // Keep the line and go on with the next one where it
+                    // starts, unless the line is a misspelled keyword.
+                    let next_line = Block::starts_line(parser.tokens.peek()) && Block::misspelled_keyword(line).is_none();
+                    // An invalid token in between, such as a look-alike of
+                    // `;`, was reported instead.
+                    let gap = Span::new(end, parser.tokens.peek_span().start);
+                    if !(next_line && parser.tokens.skipped_error_in(gap)) {
+                        parser.record(error);
+                    }
+                    if next_line {
+                        continue;
+                    }
+                    parser.tokens.next();
+                    block.lines.pop();
+                    Block::recover(parser, start);
+                    block.lines.push(Line::Error(start.to(parser.tokens.span())));
+                },
             }
         }
-        Ok(Block { lines: lines })
+        Ok(block)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::frontend;
+    use crate::lint::LintLevels;
     use crate::parser::Parser;
 
     use super::*;
 
-    fn parse_block(input: &'static str) -> Result<Block, String> {
-        let mut parser = Parser::try_from(input as &'static str).map_err(|e| e.to_string())?;
-        Block::parse(&mut parser)
+    fn parse_block(input: &str) -> Result<Block, CompileError> {
+        let mut parser = Parser::from(input);
+        let block = Block::parse(&mut parser)?;
+        match parser.take_errors().into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(block),
+        }
     }
 
     #[test]
@@ -130,12 +383,12 @@ mod tests {
         let block = result.unwrap();
         assert_eq!(block.lines.len(), 1);
         match &block.lines[0] {
-            Line::LetStatement { name, value, type_ } => {
+            Line::LetStatement { name, value, type_, .. } => {
                 assert_eq!(name, "x");
                 assert!(type_.is_some());
-                assert_eq!(type_.as_ref().unwrap(), "i32");
+                assert_eq!(type_.as_ref().unwrap().0, "i32");
                 match value {
-                    Expression::Literal(literal) => assert_eq!(*literal, 42.),
+                    Expression::Literal(literal, _) => assert_eq!(*literal, 42.),
                     _ => panic!("Expected a literal expression with value 42"),
                 }
             }
@@ -151,11 +404,11 @@ mod tests {
         let block = result.unwrap();
         assert_eq!(block.lines.len(), 1);
         match &block.lines[0] {
-            Line::LetStatement { name, value, type_ } => {
+            Line::LetStatement { name, value, type_, .. } => {
                 assert_eq!(name, "x");
                 assert!(type_.is_none());
                 match value {
-                    Expression::Literal(literal) => assert_eq!(*literal, 42.),
+                    Expression::Literal(literal, _) => assert_eq!(*literal, 42.),
                     _ => panic!("Expected a literal expression with value 42"),
                 }
             }
@@ -184,12 +437,12 @@ mod tests {
         let block = result.unwrap();
         assert_eq!(block.lines.len(), 2);
         match &block.lines[0] {
-            Line::LetStatement { name, value, type_ } => {
+            Line::LetStatement { name, value, type_, .. } => {
                 assert_eq!(name, "x");
                 assert!(type_.is_some());
-                assert_eq!(type_.as_ref().unwrap(), "i32");
+                assert_eq!(type_.as_ref().unwrap().0, "i32");
                 match value {
-                    Expression::Literal(literal) => assert_eq!(*literal, 42.),
+                    Expression::Literal(literal, _) => assert_eq!(*literal, 42.),
                     _ => panic!("Expected a literal expression with value 42"),
                 }
             }
@@ -209,8 +462,109 @@ mod tests {
         let block = result.unwrap();
         assert_eq!(block.lines.len(), 1);
         match &block.lines[0] {
-            Line::ReturnStatement(Expression::Literal(42.)) => (),
+            Line::ReturnStatement(Expression::Literal(42., _)) => (),
             _ => panic!("Expected a return statement"),
         }
     }
+
+    #[test]
+    fn test_keyword_as_let_name() {
+        let input = "{ let while := 42; }";
+        let result = parse_block(input);
+        match result {
+            Err(e) => assert!(e.notes[0].contains("reserved"), "unexpected error: {:?}", e),
+            Ok(_) => panic!("Expected an error for a keyword used as a name"),
+        }
+    }
+
+    #[test]
+    fn test_recover_from_errors_in_lines() {
+        let mut parser = Parser::from("{ let x := ; 1 + ; let y := 2; y }");
+        let block = Block::parse(&mut parser).expect("the block itself is well-formed");
+        assert_eq!(block.lines.len(), 4);
+        assert!(matches!(block.lines[0], Line::Error(_)));
+        assert!(matches!(block.lines[1], Line::Error(_)));
+        assert!(matches!(block.lines[2], Line::LetStatement { .. }));
+        assert!(matches!(block.lines[3], Line::ReturnStatement(Expression::Variable(..))));
+        let errors = parser.take_errors();
+        assert_eq!(errors.len(), 2, "unexpected errors: {:?}", errors);
+    }
+
+    #[test]
+    fn test_recover_from_missing_semicolon() {
+        let mut parser = Parser::from("{ 1 2; 3 }");
+        let block = Block::parse(&mut parser).unwrap();
+        assert!(block.has_value());
+        assert_eq!(parser.take_errors().len(), 1);
+    }
+
+    #[test]
+    fn test_keep_lines_missing_semicolon() {
+        let checked = frontend::check("{ let x := 1 let y := 2; x + y }", &LintLevels::default());
+        let errors = checked.errors.iter().map(|error| error.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(errors, ["Expected ';' but found keyword: let"]);
+        assert_eq!(checked.errors[0].span, Some(Span::new(13, 16)));
+        // Only the look-alike of `;` is reported.
+        let checked = frontend::check("{ let x := 1 \u{ff1b} x }", &LintLevels::default());
+        assert_eq!(checked.errors.len(), 1, "{:?}", checked.errors);
+        assert!(checked.errors[0].message.contains("looks like ';'"), "{:?}", checked.errors);
+    }
+
+    #[test]
+    fn test_block_span() {
+        assert_eq!(parse_block("{ 1; { 2 } }").unwrap().span, Span::new(0, 12));
+        assert_eq!(parse_block("{}").unwrap().span, Span::new(0, 2));
+        let mut parser = Parser::from("1; 2 ");
+        assert_eq!(Block::parse_entry(&mut parser).span, Span::new(0, 5));
+    }
+
+    #[test]
+    fn test_unclosed_block() {
+        let error = parse_block("{ 1;").unwrap_err();
+        assert_eq!(error.labels[0].message, "unclosed block");
+    }
+
+    #[test]
+    fn test_misspelled_keyword() {
+        let error = parse_block("{ retrun 1; }").unwrap_err();
+        assert_eq!(error.message, "Expected ';' but found number: 1");
+        assert_eq!(error.suggestions[0].replacement, "return");
+        assert_eq!(error.suggestions[0].span, Span::new(2, 8));
+    }
+
+    #[test]
+    fn test_suggest_semicolon() {
+        let error = parse_block("{ let x := 1\n  x }").unwrap_err();
+        assert_eq!(error.suggestions[0].span, Span::new(12, 12));
+        assert_eq!(error.suggestions[0].replacement, ";");
+        assert_eq!(error.suggestions[0].applicability, Applicability::MachineApplicable);
+        let error = parse_block("{ 1 ) }").unwrap_err();
+        assert_eq!(error.suggestions[0].applicability, Applicability::MaybeIncorrect);
+    }
+
+    #[test]
+    fn test_suggest_colon_equals() {
+        let error = parse_block("{ let x = 1; x }").unwrap_err();
+        assert_eq!(error.suggestions[0].span, Span::new(8, 9));
+        assert_eq!(error.suggestions[0].replacement, ":=");
+    }
+
+    #[test]
+    fn test_asserts() {
+        let block = parse_block("{ assert(1 + 1 == 2); assert(x<=y) }").unwrap();
+        assert!(!block.has_value());
+        assert!(matches!(block.lines[0], Line::Assert { comparison: Comparison::Eq, span: Span { start: 9, end: 19 }, .. }));
+        assert!(matches!(block.lines[1], Line::Assert { comparison: Comparison::Le, .. }));
+        let error = parse_block("{ assert(x = 1); }").unwrap_err();
+        assert_eq!(error.suggestions[0].replacement, "==");
+        assert!(parse_block("{ assert(x < = 1); }").is_err());
+        assert!(parse_block("{ assert(x); }").is_err());
+    }
+
+    #[test]
+    fn test_keyword_as_type() {
+        let input = "{ let x: fn = 42; }";
+        let result = parse_block(input);
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,174 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A stable code identifying a kind of error, e.g. `E0011`, with a long
+/// explanation shown by `lang --explain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ErrorCode {
+    UnexpectedToken,
+    UnclosedDelimiter,
+    MissingSemicolon,
+    LetWithoutColon,
+    ReservedKeyword,
+    InvalidNumber,
+    InvalidCharacter,
+    ConfusableCharacter,
+    MisplacedAttribute,
+    BlockWithoutValue,
+    UnknownVariable,
+    Codegen,
+    UnknownType,
+    MismatchedTypes,
+    NestingTooDeep,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 15] = [
+        ErrorCode::UnexpectedToken,
+        ErrorCode::UnclosedDelimiter,
+        ErrorCode::MissingSemicolon,
+        ErrorCode::LetWithoutColon,
+        ErrorCode::ReservedKeyword,
+        ErrorCode::InvalidNumber,
+        ErrorCode::InvalidCharacter,
+        ErrorCode::ConfusableCharacter,
+        ErrorCode::MisplacedAttribute,
+        ErrorCode::BlockWithoutValue,
+        ErrorCode::UnknownVariable,
+        ErrorCode::Codegen,
+        ErrorCode::UnknownType,
+        ErrorCode::MismatchedTypes,
+        ErrorCode::NestingTooDeep,
+    ];
+
+    /// The code as written in diagnostics. Codes are never reused once an
+    /// error is removed.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => "E0001",
+            ErrorCode::UnclosedDelimiter => "E0002",
+            ErrorCode::MissingSemicolon => "E0003",
+            ErrorCode::LetWithoutColon => "E0004",
+            ErrorCode::ReservedKeyword => "E0005",
+            ErrorCode::InvalidNumber => "E0006",
+            ErrorCode::InvalidCharacter => "E0007",
+            ErrorCode::ConfusableCharacter => "E0008",
+            ErrorCode::MisplacedAttribute => "E0009",
+            ErrorCode::BlockWithoutValue => "E0010",
+            ErrorCode::UnknownVariable => "E0011",
+            ErrorCode::Codegen => "E0012",
+            ErrorCode::UnknownType => "E0013",
+            ErrorCode::MismatchedTypes => "E0014",
+            ErrorCode::NestingTooDeep => "E0015",
+        }
+    }
+
+    /// The explanation of the error in Markdown, with an example of code
+    /// causing it followed by the same code fixed.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => include_str!("explanations/E0001.md"),
+            ErrorCode::UnclosedDelimiter => include_str!("explanations/E0002.md"),
+            ErrorCode::MissingSemicolon => include_str!("explanations/E0003.md"),
+            ErrorCode::LetWithoutColon => include_str!("explanations/E0004.md"),
+            ErrorCode::ReservedKeyword => include_str!("explanations/E0005.md"),
+            ErrorCode::InvalidNumber => include_str!("explanations/E0006.md"),
+            ErrorCode::InvalidCharacter => include_str!("explanations/E0007.md"),
+            ErrorCode::ConfusableCharacter => include_str!("explanations/E0008.md"),
+            ErrorCode::MisplacedAttribute => include_str!("explanations/E0009.md"),
+            ErrorCode::BlockWithoutValue => include_str!("explanations/E0010.md"),
+            ErrorCode::UnknownVariable => include_str!("explanations/E0011.md"),
+            ErrorCode::Codegen => include_str!("explanations/E0012.md"),
+            ErrorCode::UnknownType => include_str!("explanations/E0013.md"),
+            ErrorCode::MismatchedTypes => include_str!("explanations/E0014.md"),
+            ErrorCode::NestingTooDeep => include_str!("explanations/E0015.md"),
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = String;
+    /// Parses `E0011`, also accepting `e0011` and `0011`.
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let digits = code.strip_prefix(['E', 'e']).unwrap_or(code);
+        ErrorCode::ALL
+            .into_iter()
+            .find(|c| c.as_str()[1..] == *digits)
+            .ok_or_else(|| format!("unknown error code '{}'", code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend;
+    use crate::lint::LintLevels;
+
+    /// The code blocks of an explanation with their info string.
+    fn examples(explanation: &str) -> Vec<(&str, String)> {
+        let mut examples = Vec::new();
+        let mut lines = explanation.lines();
+        while let Some(line) = lines.next() {
+            if let Some(info) = line.strip_prefix("```") {
+                let code = lines.by_ref().take_while(|line| *line != "```").collect::<Vec<_>>().join("\n");
+                examples.push((info, code));
+            }
+        }
+        examples
+    }
+
+    fn codes(text: &str) -> Vec<Option<ErrorCode>> {
+        frontend::check(text, &LintLevels::default()).errors.iter().map(|e| e.code).collect()
+    }
+
+    #[test]
+    fn test_parse_codes() {
+        for code in ErrorCode::ALL {
+            assert_eq!(code.as_str().parse(), Ok(code));
+        }
+        assert_eq!("e0011".parse(), Ok(ErrorCode::UnknownVariable));
+        assert_eq!("0011".parse(), Ok(ErrorCode::UnknownVariable));
+        assert!("E9999".parse::<ErrorCode>().is_err());
+        assert!("".parse::<ErrorCode>().is_err());
+    }
+
+    /// The first example of an explanation must cause its error, and the
+    /// second one must compile.
+    #[test]
+    fn test_explanation_examples() {
+        for code in ErrorCode::ALL {
+            let examples = examples(code.explanation());
+            assert_eq!(examples.len(), 2, "{} needs a bad and a fixed example", code);
+            let (info, bad) = &examples[0];
+            assert!(info.starts_with("compile_fail") || *info == "ignore", "{}: {:?}", code, info);
+            if *info != "ignore" {
+                assert!(codes(bad).contains(&Some(code)), "{}: {:?}", code, codes(bad));
+            }
+            let (info, fixed) = &examples[1];
+            assert_eq!(*info, "", "{}", code);
+            assert_eq!(codes(fixed), [], "{}", code);
+        }
+    }
+
+    /// Every error the front-end reports has a code, and so an explanation.
+    #[test]
+    fn test_errors_have_codes() {
+        let programs = [
+            "}", "{", "(", ")", "+", "{ let }", "let x := 1", "1 +; 2", "#![", "#[x] 1", "{ ;;; }", "§", "1 ＋ 2", "1a",
+            "{ let x = 1; x }", "{ let let := 1; 2 }", "{ 1; }", "x", "{ #[a] }", "{ 1; #![a] 2 }", "(1 + 2",
+            "{ let x := 1\n  x }", "{ lett x := 1; x }", "{ 1 + 2", "1 as i32", "{ let x: i64 = 1; x }", "1 as i64 + 2",
+            "1 as",
+        ];
+        for program in programs {
+            let codes = codes(program);
+            assert!(!codes.is_empty(), "{:?} has no errors", program);
+            assert!(codes.iter().all(Option::is_some), "{:?}: {:?}", program, codes);
+        }
+    }
+}
@@ -1,30 +1,111 @@
-use inkwell::builder::Builder;
+use std::collections::HashMap;
+
+use inkwell::builder::{Builder, BuilderError};
 use inkwell::context::Context;
 use inkwell::execution_engine::{ExecutionEngine, JitFunction};
 use inkwell::module::Module;
-use inkwell::values::AnyValueEnum;
+use inkwell::types::{BasicTypeEnum, PointerType};
+use inkwell::values::{AnyValueEnum, BasicValueEnum, FloatValue, FunctionValue, GlobalValue, IntValue};
+use inkwell::{FloatPredicate, IntPredicate, OptimizationLevel};
 
-use crate::block::Block;
-use crate::expression::Expression;
+use crate::block::{Block, Line};
+use crate::code::ErrorCode;
+use crate::error::CompileError;
+use crate::expression::{Comparison, Expression};
 use crate::parser::Ast;
+use crate::runtime;
+use crate::source::SourceFile;
+use crate::span::Span;
+use crate::testing;
+use crate::typeck::Type;
+use crate::value::Value;
 
 type JitMain = unsafe extern "C" fn(u64, u64) -> f64;
 
+/// A variable stored outside of the compiled code, at a fixed address, e.g.
+/// one defined by an entry of the REPL.
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub type_: Type,
+    /// The address of 8 bytes holding the value.
+    pub address: usize,
+}
+
 pub struct CodeGen<'ctx> {
     pub context: &'ctx Context,
     pub module: Module<'ctx>,
     pub builder: Builder<'ctx>,
     pub execution_engine: ExecutionEngine<'ctx>,
+    /// Values of the variables of each block being compiled, innermost last.
+    scopes: Vec<HashMap<String, BasicValueEnum<'ctx>>>,
+    /// The program being compiled, to tell where runtime checks fail.
+    source: Option<SourceFile>,
+    /// Whether integer operations are checked at runtime, see `check`.
+    checks: bool,
+    /// Whether failed checks return from the function, see `runtime::take_trap`,
+    /// instead of exiting.
+    recover_traps: bool,
+    /// How many entries `compile_entry` compiled.
+    entries: usize,
 }
 
+impl From<BuilderError> for CompileError {
+    fn from(error: BuilderError) -> Self {
+        CompileError::codegen(error.to_string())
+    }
+}
 
 impl<'ctx> CodeGen<'ctx> {
-    pub fn compile<T: Compilable>(&mut self, obj: &T) -> Result<inkwell::values::AnyValueEnum<'ctx>, String> 
+    pub fn new(context: &'ctx Context) -> Result<Self, CompileError> {
+        let module = context.create_module("sum");
+        let execution_engine = module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .map_err(|e| CompileError::codegen(e.to_string()))?;
+        Ok(CodeGen {
+            context,
+            module,
+            builder: context.create_builder(),
+            execution_engine,
+            scopes: Vec::new(),
+            source: None,
+            checks: true,
+            recover_traps: false,
+            entries: 0,
+        })
+    }
+
+    pub fn with_source(mut self, source: &SourceFile) -> Self {
+        self.set_source(source);
+        self
+    }
+
+    pub fn set_source(&mut self, source: &SourceFile) {
+        self.source = Some(source.clone());
+    }
+
+    /// Disables the runtime checks of integer operations, making division by
+    /// zero, overflowing divisions and out-of-range conversions undefined.
+    pub fn with_checks(mut self, checks: bool) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Makes failed runtime checks return from the function being run
+    /// instead of exiting the process, as the REPL goes on after them.
+    pub fn with_recoverable_traps(mut self) -> Self {
+        self.recover_traps = true;
+        self
+    }
+
+    pub fn compile<T: Compilable>(&mut self, obj: &T) -> Result<inkwell::values::AnyValueEnum<'ctx>, CompileError>
     {
         obj.compile(self)
     }
 
-    pub fn compile_main<T: Compilable>(&mut self, obj: &T) -> Result<JitFunction<JitMain>, String> {
+    /// Compiles the items of a program into a function returning the value
+    /// of the last one.
+    pub fn compile_main(&mut self, program: &[Ast]) -> Result<JitFunction<'_, JitMain>, CompileError> {
         let f64_type = self.context.f64_type();
         let i64_type = self.context.i64_type();
         let fn_type = f64_type.fn_type(&[i64_type.into(), i64_type.into()], false);
@@ -32,46 +113,307 @@ impl<'ctx> CodeGen<'ctx> {
         let basic_block = self.context.append_basic_block(function, "entry");
 
         self.builder.position_at_end(basic_block);
-        
-        let compiled = self.compile(obj)?;
 
-        self.builder.build_return(Some(&compiled.into_float_value())).unwrap();
+        let mut value = f64_type.const_zero();
+        for ast in program.iter().filter(|ast| !matches!(ast, Ast::Test(_))) {
+            value = match self.compile(ast)? {
+                // The result is shown as a number either way.
+                AnyValueEnum::IntValue(value) => self.builder.build_signed_int_to_float(value, f64_type, "result")?,
+                value => float(value)?,
+            };
+        }
 
-        unsafe { self.execution_engine.get_function("sum").map_err(|e| e.to_string()) }
+        self.builder.build_return(Some(&value))?;
+
+        unsafe { self.execution_engine.get_function("sum").map_err(|e| CompileError::codegen(e.to_string())) }
     }
+
+    /// Compiles the bodies of tests into one function each, returning 0.
+    pub fn compile_tests(&mut self, tests: &[&Block]) -> Result<Vec<JitFunction<'_, JitMain>>, CompileError> {
+        let f64_type = self.context.f64_type();
+        let i64_type = self.context.i64_type();
+        let fn_type = f64_type.fn_type(&[i64_type.into(), i64_type.into()], false);
+        for (i, test) in tests.iter().enumerate() {
+            let function = self.module.add_function(&format!("test{}", i), fn_type, None);
+            self.builder.position_at_end(self.context.append_basic_block(function, "entry"));
+            self.scopes.push(HashMap::new());
+            let value = compile_lines(&test.lines, self);
+            self.scopes.pop();
+            value?;
+            self.builder.build_return(Some(&f64_type.const_zero()))?;
+        }
+
+        (0..tests.len())
+            .map(|i| unsafe {
+                self.execution_engine.get_function(&format!("test{}", i)).map_err(|e| CompileError::codegen(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Compiles an entry of the REPL into a function, in a module of its own
+    /// since compiled modules cannot change. `inputs` are the variables of
+    /// the previous entries, `outputs` where to store those the entry defines,
+    /// by name, and `result` where to store the value of the entry.
+    pub fn compile_entry(
+        &mut self,
+        block: &Block,
+        inputs: &[(String, Value)],
+        outputs: &[Global],
+        result: Option<&Global>,
+    ) -> Result<JitFunction<'_, JitMain>, CompileError> {
+        if self.module.get_first_function().is_some() {
+            self.module = self.context.create_module("entry");
+            self.execution_engine
+                .add_module(&self.module)
+                .map_err(|_| CompileError::codegen("Cannot add a module to the execution engine"))?;
+        }
+        self.entries += 1;
+        let name = format!("entry{}", self.entries);
+        let f64_type = self.context.f64_type();
+        let i64_type = self.context.i64_type();
+        let fn_type = f64_type.fn_type(&[i64_type.into(), i64_type.into()], false);
+        let function = self.module.add_function(&name, fn_type, None);
+        self.builder.position_at_end(self.context.append_basic_block(function, "entry"));
+
+        let scope = inputs.iter().map(|(name, value)| (name.clone(), self.constant(*value))).collect();
+        self.scopes.push(scope);
+        let value = compile_lines(&block.lines, self);
+        let scope = self.scopes.pop().expect("the scope of the entry is left");
+        let value = value?;
+
+        for output in outputs {
+            if let Some(value) = scope.get(&output.name) {
+                self.builder.build_store(self.global(output).as_pointer_value(), *value)?;
+            }
+        }
+        if let (Some(result), Some(value)) = (result, value) {
+            self.builder.build_store(self.global(result).as_pointer_value(), basic(value)?)?;
+        }
+        self.builder.build_return(Some(&f64_type.const_zero()))?;
+
+        unsafe { self.execution_engine.get_function(&name).map_err(|e| CompileError::codegen(e.to_string())) }
+    }
+
+    /// Declares a global of the current module stored at its address.
+    fn global(&self, global: &Global) -> GlobalValue<'ctx> {
+        let name = format!("global.{:x}", global.address);
+        if let Some(value) = self.module.get_global(&name) {
+            return value;
+        }
+        let value = self.module.add_global(self.basic_type(global.type_), None, &name);
+        self.execution_engine.add_global_mapping(&value, global.address);
+        value
+    }
+
+    fn constant(&self, value: Value) -> BasicValueEnum<'ctx> {
+        match value {
+            Value::F64(value) => self.context.f64_type().const_float(value).into(),
+            Value::I64(value) => self.context.i64_type().const_int(value as u64, true).into(),
+        }
+    }
+
+    fn basic_type(&self, type_: Type) -> BasicTypeEnum<'ctx> {
+        match type_ {
+            Type::F64 => self.context.f64_type().into(),
+            Type::I64 => self.context.i64_type().into(),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Option<BasicValueEnum<'ctx>> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    /// The runtime routine reporting failed checks, see `runtime::trap` and
+    /// `runtime::record_trap`, which takes a pointer of type `ptr_type`.
+    fn trap_function(&self, ptr_type: PointerType<'ctx>) -> FunctionValue<'ctx> {
+        let (name, address) = match self.recover_traps {
+            false => ("lang_trap", runtime::trap as *const () as usize),
+            true => ("lang_record_trap", runtime::record_trap as *const () as usize),
+        };
+        if let Some(function) = self.module.get_function(name) {
+            return function;
+        }
+        let fn_type = self.context.void_type().fn_type(&[ptr_type.into()], false);
+        let function = self.module.add_function(name, fn_type, None);
+        self.execution_engine.add_global_mapping(&function, address);
+        function
+    }
+
+    /// Stops the program with "`message` at file:line:column" when `failed`
+    /// is true at runtime, or returns from the function with recoverable
+    /// traps.
+    fn check(&mut self, failed: IntValue<'ctx>, message: &str, span: Span) -> Result<(), CompileError> {
+        let function = self
+            .builder
+            .get_insert_block()
+            .and_then(|block| block.get_parent())
+            .ok_or_else(|| CompileError::codegen("Cannot insert a runtime check outside of a function"))?;
+        let trap_block = self.context.append_basic_block(function, "trap");
+        let ok_block = self.context.append_basic_block(function, "ok");
+        self.builder.build_conditional_branch(failed, trap_block, ok_block)?;
+
+        self.builder.position_at_end(trap_block);
+        let message = match &self.source {
+            Some(source) => {
+                let (line, column) = source.line_col(span.start);
+                format!("{} at {}:{}:{}", message, source.name, line, column)
+            },
+            None => message.to_string(),
+        };
+        let message = self.builder.build_global_string_ptr(&message, "trap_message")?.as_pointer_value();
+        self.builder.build_call(self.trap_function(message.get_type()), &[message.into()], "")?;
+        match self.recover_traps {
+            true => self.builder.build_return(Some(&self.context.f64_type().const_zero()))?,
+            false => self.builder.build_unreachable()?,
+        };
+
+        self.builder.position_at_end(ok_block);
+        Ok(())
+    }
+
+    /// Checks that `left <comparison> right` holds, whether runtime checks
+    /// are enabled or not.
+    fn assert(&mut self, left: &Expression, comparison: Comparison, right: &Expression, span: Span) -> Result<(), CompileError> {
+        let (x, y) = (left.compile(self)?, right.compile(self)?);
+        let holds = match (x, y) {
+            (AnyValueEnum::FloatValue(x), AnyValueEnum::FloatValue(y)) => {
+                // Ordered comparisons are false for NaN, and `!=` true.
+                let predicate = match comparison {
+                    Comparison::Eq => FloatPredicate::OEQ,
+                    Comparison::Ne => FloatPredicate::UNE,
+                    Comparison::Lt => FloatPredicate::OLT,
+                    Comparison::Le => FloatPredicate::OLE,
+                    Comparison::Gt => FloatPredicate::OGT,
+                    Comparison::Ge => FloatPredicate::OGE,
+                };
+                self.builder.build_float_compare(predicate, x, y, "holds")?
+            },
+            (AnyValueEnum::IntValue(x), AnyValueEnum::IntValue(y)) => {
+                let predicate = match comparison {
+                    Comparison::Eq => IntPredicate::EQ,
+                    Comparison::Ne => IntPredicate::NE,
+                    Comparison::Lt => IntPredicate::SLT,
+                    Comparison::Le => IntPredicate::SLE,
+                    Comparison::Gt => IntPredicate::SGT,
+                    Comparison::Ge => IntPredicate::SGE,
+                };
+                self.builder.build_int_compare(predicate, x, y, "holds")?
+            },
+            (x, y) => {
+                let message = format!("Cannot compare {:?} and {:?}", x.get_type(), y.get_type());
+                return Err(CompileError::type_(message, span).with_code(ErrorCode::MismatchedTypes));
+            },
+        };
+        let failed = self.builder.build_not(holds, "failed")?;
+        let message = testing::assertion_failed(self.source.as_ref(), span);
+        self.check(failed, &message, span)
+    }
+
+    fn arithmetic(&mut self, operator: char, a: &Expression, b: &Expression, span: Span) -> Result<AnyValueEnum<'ctx>, CompileError> {
+        let (x, y) = (a.compile(self)?, b.compile(self)?);
+        let builder = &self.builder;
+        Ok(match (x, y) {
+            (AnyValueEnum::FloatValue(x), AnyValueEnum::FloatValue(y)) => match operator {
+                '+' => builder.build_float_add(x, y, "sum")?,
+                '-' => builder.build_float_sub(x, y, "sub")?,
+                '*' => builder.build_float_mul(x, y, "mul")?,
+                _ => builder.build_float_div(x, y, "div")?,
+            }
+            .into(),
+            (AnyValueEnum::IntValue(x), AnyValueEnum::IntValue(y)) => match operator {
+                '+' => builder.build_int_add(x, y, "sum")?,
+                '-' => builder.build_int_sub(x, y, "sub")?,
+                '*' => builder.build_int_mul(x, y, "mul")?,
+                _ => self.divide(x, y, span)?,
+            }
+            .into(),
+            (x, y) => {
+                let message = format!("Cannot apply '{}' to {:?} and {:?}", operator, x.get_type(), y.get_type());
+                return Err(CompileError::type_(message, span).with_code(ErrorCode::MismatchedTypes));
+            },
+        })
+    }
+
+    /// Signed division, which LLVM leaves undefined for a zero divisor and
+    /// for `i64::MIN / -1`.
+    fn divide(&mut self, x: IntValue<'ctx>, y: IntValue<'ctx>, span: Span) -> Result<IntValue<'ctx>, CompileError> {
+        if self.checks {
+            let i64_type = self.context.i64_type();
+            let zero = self.builder.build_int_compare(IntPredicate::EQ, y, i64_type.const_zero(), "is_zero")?;
+            self.check(zero, "division by zero", span)?;
+            let min = i64_type.const_int(i64::MIN as u64, true);
+            let is_min = self.builder.build_int_compare(IntPredicate::EQ, x, min, "is_min")?;
+            let is_minus_one = self.builder.build_int_compare(IntPredicate::EQ, y, i64_type.const_all_ones(), "is_minus_one")?;
+            let overflow = self.builder.build_and(is_min, is_minus_one, "overflow")?;
+            self.check(overflow, "attempt to divide with overflow", span)?;
+        }
+        Ok(self.builder.build_int_signed_div(x, y, "div")?)
+    }
+
+    fn convert(&mut self, value: AnyValueEnum<'ctx>, type_: Type, span: Span) -> Result<AnyValueEnum<'ctx>, CompileError> {
+        let (f64_type, i64_type) = (self.context.f64_type(), self.context.i64_type());
+        Ok(match (value, type_) {
+            (AnyValueEnum::FloatValue(value), Type::I64) => {
+                if self.checks {
+                    // Unordered comparisons are true for NaN, which is out of
+                    // range as well.
+                    let (min, limit) = (f64_type.const_float(-9223372036854775808.), f64_type.const_float(9223372036854775808.));
+                    let below = self.builder.build_float_compare(FloatPredicate::ULT, value, min, "below")?;
+                    let above = self.builder.build_float_compare(FloatPredicate::UGE, value, limit, "above")?;
+                    let out_of_range = self.builder.build_or(below, above, "out_of_range")?;
+                    self.check(out_of_range, "out-of-range conversion to i64", span)?;
+                }
+                self.builder.build_float_to_signed_int(value, i64_type, "as_i64")?.into()
+            },
+            (AnyValueEnum::IntValue(value), Type::F64) => self.builder.build_signed_int_to_float(value, f64_type, "as_f64")?.into(),
+            (value @ AnyValueEnum::FloatValue(_), Type::F64) | (value @ AnyValueEnum::IntValue(_), Type::I64) => value,
+            (value, type_) => return Err(CompileError::codegen(format!("Cannot convert {:?} to {}", value.get_type(), type_))),
+        })
+    }
+}
+
+/// The value of an expression as an `f64`.
+fn float(value: AnyValueEnum) -> Result<FloatValue, CompileError> {
+    match value {
+        AnyValueEnum::FloatValue(value) => Ok(value),
+        value => Err(CompileError::codegen(format!("Expected a number but found {:?}", value.get_type()))),
+    }
+}
+
+/// The value of an expression as one which can be stored in a variable.
+fn basic(value: AnyValueEnum) -> Result<BasicValueEnum, CompileError> {
+    BasicValueEnum::try_from(value)
+        .map_err(|_| CompileError::codegen(format!("Expected a value but found {:?}", value.get_type())))
 }
 
 pub trait Compilable {
-    fn compile<'ctx>(&self, code_gen: &CodeGen<'ctx>) -> Result<inkwell::values::AnyValueEnum<'ctx>, String>;
+    fn compile<'ctx>(&self, code_gen: &mut CodeGen<'ctx>) -> Result<inkwell::values::AnyValueEnum<'ctx>, CompileError>;
 }
 
 impl Compilable for Expression {
-    fn compile<'ctx>(&self, code_gen: &CodeGen<'ctx>) -> Result<AnyValueEnum<'ctx>, String> {
+    fn compile<'ctx>(&self, code_gen: &mut CodeGen<'ctx>) -> Result<AnyValueEnum<'ctx>, CompileError> {
         Ok(match self {
-            Expression::Literal(x) => {
+            Expression::Literal(x, _) => {
                 let i64_type = code_gen.context.f64_type();
-                i64_type.const_float(*x as f64).into()
+                i64_type.const_float(*x).into()
             },
-            Expression::Variable(_) => todo!(),
-            Expression::Add(a, b) => {
-                let x = a.compile(code_gen)?;
-                let y = b.compile(code_gen)?;
-                code_gen.builder.build_float_add(x.into_float_value(), y.into_float_value(), "sum").unwrap().into()
+            Expression::Variable(name, span) => match code_gen.variable(name) {
+                Some(value) => value.into(),
+                None => {
+                    let error = CompileError::resolve(format!("Cannot find variable '{}' in this scope", name), *span);
+                    return Err(error.with_code(ErrorCode::UnknownVariable));
+                },
             },
-            Expression::Sub(a, b) => {
-                let x = a.compile(code_gen)?;
-                let y = b.compile(code_gen)?;
-                code_gen.builder.build_float_sub(x.into_float_value(), y.into_float_value(), "sub").unwrap().into()
-            },
-            Expression::Mul(a, b) => {
-                let x = a.compile(code_gen)?;
-                let y = b.compile(code_gen)?;
-                code_gen.builder.build_float_mul(x.into_float_value(), y.into_float_value(), "mul").unwrap().into()
-            },
-            Expression::Div(a, b) => {
-                let x = a.compile(code_gen)?;
-                let y = b.compile(code_gen)?;
-                code_gen.builder.build_float_div(x.into_float_value(), y.into_float_value(), "div").unwrap().into()
+            Expression::Add(a, b, span) => code_gen.arithmetic('+', a, b, *span)?,
+            Expression::Sub(a, b, span) => code_gen.arithmetic('-', a, b, *span)?,
+            Expression::Mul(a, b, span) => code_gen.arithmetic('*', a, b, *span)?,
+            Expression::Div(a, b, span) => code_gen.arithmetic('/', a, b, *span)?,
+            Expression::Cast(value, type_, span) => {
+                let type_ = Type::lookup(type_).ok_or_else(|| {
+                    CompileError::type_(format!("Cannot find type '{}'", type_), *span).with_code(ErrorCode::UnknownType)
+                })?;
+                let value = value.compile(code_gen)?;
+                code_gen.convert(value, type_, *span)?
             },
             Expression::Block(b) => b.compile(code_gen)?,
         })
@@ -79,15 +421,105 @@ impl Compilable for Expression {
 }
 
 impl Compilable for Ast {
-    fn compile<'ctx>(&self, code_gen: &CodeGen<'ctx>) -> Result<AnyValueEnum<'ctx>, String> {
+    fn compile<'ctx>(&self, code_gen: &mut CodeGen<'ctx>) -> Result<AnyValueEnum<'ctx>, CompileError> {
         match self {
             Ast::Expression(expr) => expr.compile(code_gen),
+            Ast::Test(_) => Err(CompileError::codegen("Tests are only compiled by `compile_tests`")),
         }
     }
 }
 
 impl Compilable for Block {
-    fn compile<'ctx>(&self, code_gen: &CodeGen<'ctx>) -> Result<AnyValueEnum<'ctx>, String> {
-        todo!()
+    /// The value of a block is the one of its first `return`; the lines after
+    /// it are not compiled.
+    fn compile<'ctx>(&self, code_gen: &mut CodeGen<'ctx>) -> Result<AnyValueEnum<'ctx>, CompileError> {
+        code_gen.scopes.push(HashMap::new());
+        let value = compile_lines(&self.lines, code_gen);
+        code_gen.scopes.pop();
+        value?.ok_or_else(|| CompileError::codegen("Cannot compile a block without return value"))
     }
-}
\ No newline at end of file
+}
+
+fn compile_lines<'ctx>(lines: &[Line], code_gen: &mut CodeGen<'ctx>) -> Result<Option<AnyValueEnum<'ctx>>, CompileError> {
+    for line in lines {
+        match line {
+            Line::Expression(expression) => {
+                expression.compile(code_gen)?;
+            },
+            Line::LetStatement { name, value, .. } => {
+                let value = basic(value.compile(code_gen)?)?;
+                if let Some(scope) = code_gen.scopes.last_mut() {
+                    scope.insert(name.clone(), value);
+                }
+            },
+            Line::ReturnStatement(expression) => return expression.compile(code_gen).map(Some),
+            Line::Assert { left, comparison, right, span } => code_gen.assert(left, *comparison, right, *span)?,
+            Line::Error(_) => return Err(CompileError::codegen("Cannot compile a line which failed to parse")),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::interp::Interpreter;
+    use crate::parser::Parser;
+
+    /// Runs a program with the JIT, checking that the interpreter agrees.
+    fn run(data: &str) -> Result<f64, CompileError> {
+        let program = Parser::from(data).collect::<Result<Vec<_>, _>>().expect("test programs are valid");
+        let context = Context::create();
+        let mut codegen = CodeGen::new(&context)?;
+        let main = codegen.compile_main(&program)?;
+        let value = unsafe { main.call(0, 0) };
+        assert_eq!(Interpreter::new().run(&program).ok(), Some(value), "the interpreter disagrees on {:?}", data);
+        Ok(value)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(run("1 + 2 * 3 - 8 / 4").unwrap(), 5.);
+    }
+
+    #[test]
+    fn test_blocks_and_variables() {
+        assert_eq!(run("{ let x := 2; let y: f64 = { let x := 3; x * x }; x + y }").unwrap(), 11.);
+        assert_eq!(run("{ return 1; 2 }").unwrap(), 1.);
+    }
+
+    #[test]
+    fn test_unknown_variable() {
+        let error = run("{ let x := 1; y }").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Resolve);
+        assert_eq!(error.message, "Cannot find variable 'y' in this scope");
+        // Variables are not visible outside of their block.
+        assert!(run("{ { let x := 1; x } + x }").is_err());
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(run("(7 as i64 / (2 as i64)) as f64").unwrap(), 3.);
+        assert_eq!(run("{ let x: i64 = 0 as i64 - 7 as i64; x / (2 as i64) }").unwrap(), -3.);
+        assert_eq!(run("{ let x := 1 as i64; x * (5 as i64) - x }").unwrap(), 4.);
+    }
+
+    /// Without checks, out-of-range conversions give an unspecified value
+    /// instead of exiting the process.
+    #[test]
+    fn test_unchecked_conversion() {
+        let program = Parser::from("{ let x := 99999999999999999999 as i64; 8 as i64 / (2 as i64) }")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let context = Context::create();
+        let mut codegen = CodeGen::new(&context).unwrap().with_checks(false);
+        let main = codegen.compile_main(&program).unwrap();
+        assert_eq!(unsafe { main.call(0, 0) }, 4.);
+    }
+
+    #[test]
+    fn test_empty_program() {
+        assert_eq!(run("").unwrap(), 0.);
+    }
+}
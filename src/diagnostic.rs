@@ -0,0 +1,420 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter, Write as _};
+use std::io::Write as _;
+use std::str::FromStr;
+
+use anstyle::{AnsiColor, Style};
+use serde::Serialize;
+
+use crate::error::{Applicability, CompileError, Label, Suggestion};
+use crate::source::SourceFile;
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    fn style(&self) -> Style {
+        match self {
+            Severity::Error => AnsiColor::BrightRed.on_default().bold(),
+            Severity::Warning => AnsiColor::BrightYellow.on_default().bold(),
+            Severity::Note => AnsiColor::BrightGreen.on_default().bold(),
+            Severity::Help => AnsiColor::BrightCyan.on_default().bold(),
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+            Severity::Help => write!(f, "help"),
+        }
+    }
+}
+
+const GUTTER: Style = AnsiColor::BrightBlue.on_default().bold();
+const MESSAGE: Style = Style::new().bold();
+
+/// A message for the user about their program, with everything needed to
+/// show it next to the source it is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    /// The primary location, underlined with `^`.
+    pub span: Option<Span>,
+    /// Secondary locations, underlined with `-`.
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            span: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Suggests replacing `span` with `replacement`, described by `message`.
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        let replacement = replacement.into();
+        self.suggestions.push(Suggestion { message: message.into(), span, replacement, applicability });
+        self
+    }
+}
+
+impl From<CompileError> for Diagnostic {
+    fn from(error: CompileError) -> Self {
+        Diagnostic::from(&error)
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: error.code.map(|code| code.as_str().to_string()),
+            message: error.message.clone(),
+            span: error.span,
+            labels: error.labels.clone(),
+            notes: error.notes.clone(),
+            help: error.help.clone(),
+            suggestions: error.suggestions.clone(),
+        }
+    }
+}
+
+/// A part of a span lying on one line, as columns in characters.
+struct Annotation<'d> {
+    line: usize,
+    start: usize,
+    end: usize,
+    primary: bool,
+    /// Only the last line of a span carries its label.
+    message: Option<&'d str>,
+}
+
+impl Diagnostic {
+    /// Annotations of every span of the diagnostic, ordered by line and
+    /// column, primary first.
+    fn annotations(&self, source: &SourceFile) -> Vec<Annotation<'_>> {
+        let spans = self
+            .span
+            .map(|span| (span, true, None))
+            .into_iter()
+            .chain(self.labels.iter().map(|label| (label.span, false, Some(label.message.as_str()))));
+        let mut annotations = Vec::new();
+        for (span, primary, message) in spans {
+            let first = source.line_index(span.start.min(source.text.len()));
+            let last = source.line_index(span.end.max(span.start).min(source.text.len()));
+            for line in first..=last {
+                let range = source.line_range(line);
+                let from = span.start.clamp(range.start, range.end);
+                let to = span.end.clamp(range.start, range.end);
+                let start = source.text[range.start..from].chars().count();
+                let end = start + source.text[from..to].chars().count();
+                annotations.push(Annotation {
+                    line,
+                    start,
+                    // Empty spans, e.g. at the end of the input, are shown
+                    // as one character.
+                    end: end.max(start + 1),
+                    primary,
+                    message: if line == last { message } else { None },
+                });
+            }
+        }
+        annotations.sort_by_key(|a| (a.line, !a.primary, a.start));
+        annotations
+    }
+
+    /// The diagnostic as displayed in a terminal, with ANSI styles which
+    /// the caller strips when colours are not wanted.
+    pub fn render(&self, source: &SourceFile) -> String {
+        let mut out = String::new();
+        self.write(&mut out, source).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write(&self, out: &mut String, source: &SourceFile) -> fmt::Result {
+        let style = self.severity.style();
+        write!(out, "{style}{}", self.severity)?;
+        if let Some(code) = &self.code {
+            write!(out, "[{}]", code)?;
+        }
+        writeln!(out, "{style:#}{MESSAGE}: {}{MESSAGE:#}", self.message)?;
+
+        let annotations = self.annotations(source);
+        let width = annotations.last().map_or(0, |a| (a.line + 1).to_string().len());
+        let pad = " ".repeat(width);
+        if let Some(span) = self.span.or_else(|| self.labels.first().map(|l| l.span)) {
+            let (line, column) = source.line_col(span.start);
+            writeln!(out, "{pad}{GUTTER}-->{GUTTER:#} {}:{}:{}", source.name, line, column)?;
+        }
+        if !annotations.is_empty() {
+            writeln!(out, "{pad} {GUTTER}|{GUTTER:#}")?;
+        }
+        let mut previous: Option<usize> = None;
+        for (i, annotation) in annotations.iter().enumerate() {
+            if previous != Some(annotation.line) {
+                if previous.is_some_and(|p| annotation.line > p + 1) {
+                    writeln!(out, "{GUTTER}...{GUTTER:#}")?;
+                }
+                let number = format!("{:>width$}", annotation.line + 1);
+                writeln!(out, "{GUTTER}{number} |{GUTTER:#} {}", source.line(annotation.line))?;
+                previous = Some(annotation.line);
+            }
+            let (marker, marker_style) = match annotation.primary {
+                true => ('^', style),
+                false => ('-', GUTTER),
+            };
+            write!(
+                out,
+                "{pad} {GUTTER}|{GUTTER:#} {}{marker_style}{}",
+                " ".repeat(annotation.start),
+                marker.to_string().repeat(annotation.end - annotation.start),
+            )?;
+            match annotation.message {
+                Some(message) => writeln!(out, " {}{marker_style:#}", message)?,
+                None => writeln!(out, "{marker_style:#}")?,
+            }
+            let attached = !self.notes.is_empty() || !self.help.is_empty() || !self.suggestions.is_empty();
+            if i + 1 == annotations.len() && attached {
+                writeln!(out, "{pad} {GUTTER}|{GUTTER:#}")?;
+            }
+        }
+        let suggestions = self
+            .suggestions
+            .iter()
+            .map(|suggestion| format!("{}: '{}'", suggestion.message, suggestion.replacement))
+            .collect::<Vec<_>>();
+        let attachments = self
+            .notes
+            .iter()
+            .map(|note| (Severity::Note, note))
+            .chain(self.help.iter().map(|help| (Severity::Help, help)))
+            .chain(suggestions.iter().map(|suggestion| (Severity::Help, suggestion)));
+        for (severity, text) in attachments {
+            writeln!(out, "{pad} {GUTTER}={GUTTER:#} {MESSAGE}{}{MESSAGE:#}: {}", severity, text)?;
+        }
+        Ok(())
+    }
+}
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Rendered with source snippets, for people.
+    #[default]
+    Human,
+    /// One JSON object per line, for tools; see `crate::json`.
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("unknown error format '{}', expected 'human' or 'json'", s)),
+        }
+    }
+}
+
+/// Prints diagnostics about one source file to stderr, coloured when it is
+/// a terminal, and counts them.
+pub struct Emitter<'s> {
+    source: &'s SourceFile,
+    format: ErrorFormat,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Codes of the errors emitted, to point to their explanation.
+    codes: BTreeSet<String>,
+}
+
+impl<'s> Emitter<'s> {
+    pub fn new(source: &'s SourceFile) -> Self {
+        Emitter { source, format: ErrorFormat::Human, errors: 0, warnings: 0, codes: BTreeSet::new() }
+    }
+
+    pub fn with_format(mut self, format: ErrorFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn emit(&mut self, diagnostic: &Diagnostic) {
+        match diagnostic.severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Note | Severity::Help => (),
+        }
+        self.codes.extend(diagnostic.code.clone());
+        let _ = match self.format {
+            ErrorFormat::Human => writeln!(anstream::stderr(), "{}", diagnostic.render(self.source)),
+            ErrorFormat::Json => writeln!(std::io::stderr(), "{}", diagnostic.to_json(self.source)),
+        };
+    }
+
+    /// Prints how many errors and warnings were emitted, if any. Tools
+    /// reading JSON count them themselves.
+    pub fn summary(&self) {
+        if self.format == ErrorFormat::Json {
+            return;
+        }
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let mut stderr = anstream::stderr();
+        let error = Severity::Error.style();
+        let warning = Severity::Warning.style();
+        if self.warnings > 0 {
+            let _ = writeln!(
+                stderr,
+                "{warning}warning{warning:#}{MESSAGE}: {} warning{} emitted{MESSAGE:#}",
+                self.warnings,
+                plural(self.warnings),
+            );
+        }
+        if self.errors > 0 {
+            let _ = writeln!(
+                stderr,
+                "{error}error{error:#}{MESSAGE}: aborting due to {} previous error{}{MESSAGE:#}",
+                self.errors,
+                plural(self.errors),
+            );
+        }
+        let codes = self.codes.iter().map(String::as_str).collect::<Vec<_>>();
+        match codes[..] {
+            [] => (),
+            [code] => {
+                let _ = writeln!(stderr, "{MESSAGE}For more information about this error, try `lang --explain {}`.{MESSAGE:#}", code);
+            },
+            [first, ..] => {
+                let _ = writeln!(
+                    stderr,
+                    "{MESSAGE}Some errors have detailed explanations: {}.{MESSAGE:#}\n\
+                     {MESSAGE}For more information about an error, try `lang --explain {}`.{MESSAGE:#}",
+                    codes.join(", "),
+                    first,
+                );
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(diagnostic: &Diagnostic, text: &str) -> String {
+        let source = SourceFile::new("test.x", text);
+        anstream::adapter::strip_str(&diagnostic.render(&source)).to_string()
+    }
+
+    #[test]
+    fn test_render_primary_span() {
+        let error = CompileError::parse("Expected ';' but found 'x'", Span::new(18, 19))
+            .with_help("add ';' here");
+        let mut diagnostic = Diagnostic::from(error);
+        diagnostic.code = Some("E0001".to_string());
+        let rendered = render(&diagnostic, "{ let y := 1;\n  1 x }");
+        assert_eq!(
+            rendered,
+            "error[E0001]: Expected ';' but found 'x'\n \
+             --> test.x:2:5\n  \
+             |\n\
+             2 |   1 x }\n  \
+             |     ^\n  \
+             |\n  \
+             = help: add ';' here\n",
+        );
+    }
+
+    #[test]
+    fn test_render_secondary_labels() {
+        let error = CompileError::parse("Expected operator ')' but found end of input", Span::new(10, 10))
+            .with_label(Span::new(4, 5), "unclosed parenthesis");
+        let rendered = render(&Diagnostic::from(error), "1 + (2 * 3");
+        assert_eq!(
+            rendered,
+            "error: Expected operator ')' but found end of input\n \
+             --> test.x:1:11\n  \
+             |\n\
+             1 | 1 + (2 * 3\n  \
+             |           ^\n  \
+             |     - unclosed parenthesis\n",
+        );
+    }
+
+    #[test]
+    fn test_render_multiline_span() {
+        let error = CompileError::type_("Expected block with return value", Span::new(0, 9))
+            .with_note("blocks ending with ';' have no value");
+        let rendered = render(&Diagnostic::from(error), "{\n  30;\n} + 12");
+        assert_eq!(
+            rendered,
+            "error: Expected block with return value\n \
+             --> test.x:1:1\n  \
+             |\n\
+             1 | {\n  \
+             | ^\n\
+             2 |   30;\n  \
+             | ^^^^^\n\
+             3 | } + 12\n  \
+             | ^\n  \
+             |\n  \
+             = note: blocks ending with ';' have no value\n",
+        );
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let error = CompileError::codegen("Failed to create the execution engine");
+        let rendered = render(&Diagnostic::from(error), "");
+        assert_eq!(rendered, "error[E0012]: Failed to create the execution engine\n");
+    }
+}
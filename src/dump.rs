@@ -0,0 +1,259 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::attribute::Attribute;
+use crate::block::{Block, Line};
+use crate::expression::Expression;
+use crate::parser::Ast;
+use crate::source::SourceFile;
+use crate::span::Span;
+use crate::tokenizer::{Token, Tokenizer};
+
+/// What `--dump` prints instead of running a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dump {
+    /// The tokens with their spans.
+    Tokens,
+    /// The syntax tree, indented, with spans.
+    Ast,
+    /// The syntax tree as S-expressions, one per top-level item.
+    Sexp,
+}
+
+impl FromStr for Dump {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tokens" => Ok(Dump::Tokens),
+            "ast" => Ok(Dump::Ast),
+            "sexp" => Ok(Dump::Sexp),
+            _ => Err(format!("unknown dump '{}', expected 'tokens', 'ast' or 'sexp'", s)),
+        }
+    }
+}
+
+fn span(span: Span) -> String {
+    format!("{}..{}", span.start, span.end)
+}
+
+/// One token per line with its location, byte span and text, including
+/// the invalid ones.
+pub fn tokens(source: &SourceFile) -> String {
+    let mut tokenizer = Tokenizer::from(source.text.as_str());
+    let mut out = String::new();
+    while let Some((token, token_span)) = tokenizer.next_raw() {
+        let (line, column) = source.line_col(token_span.start);
+        let location = format!("{}:{}", line, column);
+        let token = match token {
+            Token::Number => format!("Number({})", tokenizer.slice(token_span)),
+            Token::String => format!("String({})", tokenizer.slice(token_span)),
+            Token::Symbol(symbol) => format!("Symbol({})", tokenizer.resolve(symbol)),
+            token => token.to_string(),
+        };
+        writeln!(out, "{:<8}{:<10}{}", location, span(token_span), token).unwrap();
+    }
+    out
+}
+
+/// The syntax tree of a program, one node per line indented under its
+/// parent, with the spans the nodes keep.
+pub fn tree(attributes: &[Attribute], items: &[(Ast, Span)]) -> String {
+    let mut out = String::new();
+    for attribute in attributes {
+        tree_attribute(&mut out, attribute, 0);
+    }
+    for (ast, item_span) in items {
+        writeln!(out, "Item {}", span(*item_span)).unwrap();
+        match ast {
+            Ast::Expression(expression) => tree_expression(&mut out, expression, 1),
+            Ast::Test(test) => {
+                writeln!(out, "  Test {:?} {}", test.name, span(test.name_span)).unwrap();
+                tree_block(&mut out, &test.body, 2);
+            },
+        }
+    }
+    out
+}
+
+fn tree_attribute(out: &mut String, attribute: &Attribute, depth: usize) {
+    let args = attribute.args.iter().map(|(arg, _)| arg.as_str()).collect::<Vec<_>>().join(", ");
+    let kind = if attribute.inner { "InnerAttribute" } else { "Attribute" };
+    writeln!(out, "{:depth$}{} {}({}) {}", "", kind, attribute.name, args, span(attribute.span), depth = depth * 2).unwrap();
+}
+
+fn tree_block(out: &mut String, block: &Block, depth: usize) {
+    writeln!(out, "{:depth$}Block {}", "", span(block.span), depth = depth * 2).unwrap();
+    for attribute in &block.attributes {
+        tree_attribute(out, attribute, depth + 1);
+    }
+    for (line, info) in block.lines.iter().zip(&block.info) {
+        for attribute in &info.attributes {
+            tree_attribute(out, attribute, depth + 1);
+        }
+        let indent = (depth + 1) * 2;
+        match line {
+            Line::Expression(expression) => {
+                writeln!(out, "{:indent$}Expression {}", "", span(info.span)).unwrap();
+                tree_expression(out, expression, depth + 2);
+            },
+            Line::LetStatement { name, span: name_span, value, type_ } => {
+                writeln!(out, "{:indent$}Let {}", "", span(info.span)).unwrap();
+                writeln!(out, "{:indent$}  Name {} {}", "", name, span(*name_span)).unwrap();
+                if let Some((type_, type_span)) = type_ {
+                    writeln!(out, "{:indent$}  Type {} {}", "", type_, span(*type_span)).unwrap();
+                }
+                tree_expression(out, value, depth + 2);
+            },
+            Line::ReturnStatement(expression) => {
+                writeln!(out, "{:indent$}Return {}", "", span(info.span)).unwrap();
+                tree_expression(out, expression, depth + 2);
+            },
+            Line::Assert { left, comparison, right, span: condition_span } => {
+                writeln!(out, "{:indent$}Assert {}", "", span(info.span)).unwrap();
+                writeln!(out, "{:indent$}  Comparison {} {}", "", comparison.as_str(), span(*condition_span)).unwrap();
+                tree_expression(out, left, depth + 2);
+                tree_expression(out, right, depth + 2);
+            },
+            Line::Error(error_span) => writeln!(out, "{:indent$}Error {}", "", span(*error_span)).unwrap(),
+        }
+    }
+}
+
+/// Binary operations show the span of their operator.
+fn tree_expression(out: &mut String, expression: &Expression, depth: usize) {
+    let indent = depth * 2;
+    match expression {
+        Expression::Literal(value, _) => writeln!(out, "{:indent$}Literal {}", "", value).unwrap(),
+        Expression::Variable(name, name_span) => {
+            writeln!(out, "{:indent$}Variable {} {}", "", name, span(*name_span)).unwrap()
+        },
+        Expression::Block(block) => tree_block(out, block, depth),
+        Expression::Add(a, b, operator_span)
+        | Expression::Sub(a, b, operator_span)
+        | Expression::Mul(a, b, operator_span)
+        | Expression::Div(a, b, operator_span) => {
+            let name = match expression {
+                Expression::Add(..) => "Add",
+                Expression::Sub(..) => "Sub",
+                Expression::Mul(..) => "Mul",
+                _ => "Div",
+            };
+            writeln!(out, "{:indent$}{} {}", "", name, span(*operator_span)).unwrap();
+            tree_expression(out, a, depth + 1);
+            tree_expression(out, b, depth + 1);
+        },
+        Expression::Cast(value, type_, cast_span) => {
+            writeln!(out, "{:indent$}Cast {} {}", "", type_, span(*cast_span)).unwrap();
+            tree_expression(out, value, depth + 1);
+        },
+    }
+}
+
+/// A top-level item as an S-expression, e.g. `(test "name" (assert (== 1 1)))`.
+pub fn sexp_ast(ast: &Ast) -> String {
+    match ast {
+        Ast::Expression(expression) => sexp(expression),
+        Ast::Test(test) => sexp_lines(&format!("(test {:?}", test.name), &test.body),
+    }
+}
+
+/// An expression as an S-expression, e.g. `(+ 1 (* 2 3))` for `1 + 2 * 3`.
+pub fn sexp(expression: &Expression) -> String {
+    match expression {
+        Expression::Literal(value, _) => value.to_string(),
+        Expression::Variable(name, _) => name.clone(),
+        Expression::Block(block) => sexp_lines("(block", block),
+        Expression::Add(a, b, _) | Expression::Sub(a, b, _) | Expression::Mul(a, b, _) | Expression::Div(a, b, _) => {
+            let operator = expression.operator().expect("binary operations have an operator");
+            format!("({} {} {})", operator, sexp(a), sexp(b))
+        },
+        Expression::Cast(value, type_, _) => format!("(as {} {})", sexp(value), type_),
+    }
+}
+
+fn sexp_lines(head: &str, block: &Block) -> String {
+    let lines = block.lines.iter().map(|line| match line {
+        Line::Expression(expression) => sexp(expression),
+        Line::LetStatement { name, value, type_: Some((type_, _)), .. } => {
+            format!("(let {} {} {})", name, type_, sexp(value))
+        },
+        Line::LetStatement { name, value, type_: None, .. } => format!("(let {} {})", name, sexp(value)),
+        Line::ReturnStatement(expression) => format!("(return {})", sexp(expression)),
+        Line::Assert { left, comparison, right, .. } => {
+            format!("(assert ({} {} {}))", comparison.as_str(), sexp(left), sexp(right))
+        },
+        Line::Error(_) => "(error)".to_string(),
+    });
+    std::iter::once(head.to_string()).chain(lines).collect::<Vec<_>>().join(" ") + ")"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn items(text: &str) -> Vec<(Ast, Span)> {
+        let mut parser = Parser::from(text);
+        let mut items = Vec::new();
+        while let Some(item) = parser.next() {
+            items.push((item.expect("test programs are valid"), parser.span()));
+        }
+        items
+    }
+
+    fn sexps(text: &str) -> Vec<String> {
+        items(text).iter().map(|(ast, _)| sexp_ast(ast)).collect()
+    }
+
+    #[test]
+    fn test_dump_tokens() {
+        let source = SourceFile::new("test.x", "let x§ :=\n  42");
+        assert_eq!(
+            tokens(&source),
+            "1:1     0..3      Keyword(let)\n\
+             1:5     4..5      Symbol(x)\n\
+             1:6     5..7      Error(Invalid character '§' (U+00A7) in input)\n\
+             1:8     8..9      Operator(:)\n\
+             1:9     9..10     Operator(=)\n\
+             2:3     13..15    Number(42)\n",
+        );
+    }
+
+    #[test]
+    fn test_dump_tree() {
+        assert_eq!(
+            tree(&[], &items("{ let y: i64 = 2 as i64; 1 - y * 3 }")),
+            "Item 0..36\n  \
+               Block 0..36\n    \
+                 Let 2..23\n      \
+                   Name y 6..7\n      \
+                   Type i64 9..12\n      \
+                   Cast i64 17..23\n        \
+                     Literal 2\n    \
+                 Return 25..34\n      \
+                   Sub 27..28\n        \
+                     Literal 1\n        \
+                     Mul 31..32\n          \
+                       Variable y 29..30\n          \
+                       Literal 3\n",
+        );
+        assert_eq!(
+            tree(&[], &items("test \"t\" { assert(x < 1) }")),
+            "Item 0..26\n  \
+               Test \"t\" 5..8\n    \
+                 Block 9..26\n      \
+                   Assert 11..24\n        \
+                     Comparison < 18..23\n        \
+                     Variable x 18..19\n        \
+                     Literal 1\n",
+        );
+    }
+
+    #[test]
+    fn test_dump_sexp() {
+        assert_eq!(sexps("1 + 2 * 3"), ["(+ 1 (* 2 3))"]);
+        assert_eq!(sexps("1 - 2 - 3 / x as f64"), ["(- 1 (- 2 (/ 3 (as x f64))))"]);
+        assert_eq!(sexps("{ let x := 1; return x; x } 2"), ["(block (let x 1) (return x) (return x))", "2"]);
+        assert_eq!(sexps("test \"one\" { assert(1 != x) }"), ["(test \"one\" (assert (!= 1 x)))"]);
+    }
+}
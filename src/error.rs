@@ -1,20 +1,188 @@
-// use std::{error::Error, path::{Path, PathBuf}};
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Deref, DerefMut};
 
-// use crate::tokenizer::Tokenizer;
+use serde::Serialize;
 
+use crate::code::ErrorCode;
+use crate::span::Span;
 
-// struct Location{
-//     line: u64,
-//     column: u64,
-//     file: PathBuf,
-// }
+/// The stage of the compiler an error comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Invalid characters or malformed tokens.
+    Lex,
+    /// Tokens which don't form a valid program.
+    Parse,
+    /// Names which don't refer to anything in scope.
+    Resolve,
+    /// Values used where a value of another type is expected.
+    Type,
+    /// Failures while generating or running machine code.
+    Codegen,
+    /// Failures to read the program.
+    Io,
+    /// Checks which failed while running the program.
+    Runtime,
+}
 
-// impl Location{
-//     fn fill_from(&self, tokenizer: &Tokenizer) {
-//         tokenizer
-//     }
-// }
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Lex => write!(f, "lexical"),
+            ErrorKind::Parse => write!(f, "syntax"),
+            ErrorKind::Resolve => write!(f, "name resolution"),
+            ErrorKind::Type => write!(f, "type"),
+            ErrorKind::Codegen => write!(f, "code generation"),
+            ErrorKind::Io => write!(f, "I/O"),
+            ErrorKind::Runtime => write!(f, "runtime"),
+        }
+    }
+}
 
-// trait CompilationError : Error {
-//     fn location(&self) -> Option<>
-// }
\ No newline at end of file
+/// A secondary location attached to an error, e.g. where a conflicting
+/// definition is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// How sure we are that a suggestion is what the user wants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Applicability {
+    /// The suggestion is right and can be applied without looking at it,
+    /// e.g. by `lang fix`.
+    MachineApplicable,
+    /// The suggestion may not be what the user meant.
+    MaybeIncorrect,
+}
+
+/// A change of the source which fixes an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+/// An error of any stage of the compiler. The data is boxed as the error
+/// is passed around in the `Result` of every parsing function; its fields
+/// are accessible through `Deref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError(Box<ErrorData>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorData {
+    pub kind: ErrorKind,
+    /// The code of the error, explained by `lang --explain`. Only errors
+    /// which are not about the program, such as I/O errors, have none.
+    pub code: Option<ErrorCode>,
+    pub message: String,
+    /// Where the error is, if it can be attributed to a part of the source.
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl CompileError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CompileError(Box::new(ErrorData {
+            kind,
+            code: None,
+            message: message.into(),
+            span: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }))
+    }
+
+    pub fn lex(message: impl Into<String>, span: Span) -> Self {
+        CompileError::new(ErrorKind::Lex, message).with_span(span)
+    }
+
+    pub fn parse(message: impl Into<String>, span: Span) -> Self {
+        CompileError::new(ErrorKind::Parse, message).with_span(span)
+    }
+
+    pub fn resolve(message: impl Into<String>, span: Span) -> Self {
+        CompileError::new(ErrorKind::Resolve, message).with_span(span)
+    }
+
+    pub fn type_(message: impl Into<String>, span: Span) -> Self {
+        CompileError::new(ErrorKind::Type, message).with_span(span)
+    }
+
+    pub fn codegen(message: impl Into<String>) -> Self {
+        CompileError::new(ErrorKind::Codegen, message).with_code(ErrorCode::Codegen)
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Suggests replacing `span` with `replacement`, described by `message`.
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        let replacement = replacement.into();
+        self.suggestions.push(Suggestion { message: message.into(), span, replacement, applicability });
+        self
+    }
+}
+
+impl Deref for CompileError {
+    type Target = ErrorData;
+    fn deref(&self) -> &ErrorData {
+        &self.0
+    }
+}
+
+impl DerefMut for CompileError {
+    fn deref_mut(&mut self) -> &mut ErrorData {
+        &mut self.0
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} error: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<std::io::Error> for CompileError {
+    fn from(error: std::io::Error) -> Self {
+        CompileError::new(ErrorKind::Io, error.to_string())
+    }
+}
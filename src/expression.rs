@@ -1,20 +1,92 @@
 use crate::block::Block as BlockO;
-use crate::tokenizer::Token;
+use crate::code::ErrorCode;
+use crate::error::{Applicability, CompileError};
+use crate::span::Span;
+use crate::tokenizer::{Keyword as K, Token};
 use crate::parser::{Parsable, Parser};
 
 #[derive(Debug, Clone)]
 pub enum Expression {
-    Literal(f64),
-    Variable(String),
+    /// A number, with the span of how it is written.
+    Literal(f64, Span),
+    Variable(String, Span),
     Block(BlockO),
-    Add(Box<Expression>, Box<Expression>),
-    Sub(Box<Expression>, Box<Expression>),
-    Mul(Box<Expression>, Box<Expression>),
-    Div(Box<Expression>, Box<Expression>),
+    /// Binary operations carry the span of their operator.
+    Add(Box<Expression>, Box<Expression>, Span),
+    Sub(Box<Expression>, Box<Expression>, Span),
+    Mul(Box<Expression>, Box<Expression>, Span),
+    Div(Box<Expression>, Box<Expression>, Span),
+    /// `value as type`, with the span of `as type`.
+    Cast(Box<Expression>, String, Span),
+}
+
+/// The comparison of an `assert`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+
+    /// Whether `x <comparison> y`. Only `!=` holds for NaN, as in IEEE 754.
+    pub fn holds<T: PartialOrd>(self, x: T, y: T) -> bool {
+        match self {
+            Comparison::Eq => x == y,
+            Comparison::Ne => x != y,
+            Comparison::Lt => x < y,
+            Comparison::Le => x <= y,
+            Comparison::Gt => x > y,
+            Comparison::Ge => x >= y,
+        }
+    }
+
+    /// Parses `==`, `!=`, `<`, `<=`, `>` or `>=`, written without spaces.
+    pub fn parse(parser: &mut Parser) -> Result<Comparison, CompileError> {
+        let token = parser.tokens.next();
+        let start = parser.tokens.span();
+        let equals = parser.tokens.peek() == Some(&Token::Operator('=')) && parser.tokens.peek_span().start == start.end;
+        if equals {
+            parser.tokens.next();
+        }
+        Ok(match (token, equals) {
+            (Some(Token::Operator('=')), true) => Comparison::Eq,
+            (Some(Token::Operator('!')), true) => Comparison::Ne,
+            (Some(Token::Operator('<')), false) => Comparison::Lt,
+            (Some(Token::Operator('<')), true) => Comparison::Le,
+            (Some(Token::Operator('>')), false) => Comparison::Gt,
+            (Some(Token::Operator('>')), true) => Comparison::Ge,
+            (Some(Token::Operator('=')), false) => {
+                let error = CompileError::parse("Expected comparison but found operator: =", start)
+                    .with_code(ErrorCode::UnexpectedToken);
+                return Err(error.with_suggestion(start, "==", "compare with '=='", Applicability::MachineApplicable));
+            },
+            (token, _) => {
+                let error = parser.tokens.unexpected(token, "comparison");
+                return Err(error.with_note("comparisons are '==', '!=', '<', '<=', '>' and '>='"));
+            },
+        })
+    }
 }
 
 impl Expression {
-    fn precedence(c: char) -> i8 {
+    /// How loosely an operator binds: operands of lower precedence are
+    /// grouped first.
+    pub fn precedence(c: char) -> i8 {
         match c {
             '*' | '/' => 1,
             '+' | '-' => 2,
@@ -23,64 +95,112 @@ impl Expression {
         }
     }
 
-    fn parse_prec(parser: &mut Parser, prec: i8) -> Result<Expression, String> {  
+    /// The operator of a binary operation.
+    pub fn operator(&self) -> Option<char> {
+        match self {
+            Expression::Add(..) => Some('+'),
+            Expression::Sub(..) => Some('-'),
+            Expression::Mul(..) => Some('*'),
+            Expression::Div(..) => Some('/'),
+            _ => None,
+        }
+    }
+
+    fn parse_prec(parser: &mut Parser, prec: i8) -> Result<Expression, CompileError> {  
         use Token::*;       
         use Expression::*;       
         let left = if prec == 0 {
-            if parser.tokens.peek() == Some(&Ok(Operator('{'))) {
-                let block = BlockO::parse(parser)?;
-                if !block.has_value() {
-                    return Err("Expected block with return value".to_string());
+            if parser.tokens.peek() == Some(&Operator('{')) {
+                let start = parser.tokens.peek_span();
+                let block = parser.nested(start, BlockO::parse)?;
+                if !block.has_value() && !block.has_errors() {
+                    let span = start.to(parser.tokens.span());
+                    return Err(CompileError::type_("Expected block with return value", span)
+                        .with_code(ErrorCode::BlockWithoutValue)
+                        .with_help("remove the ';' after the last expression or end the block with 'return'"));
                 }
                 Block(block)
+            } else if let Some(Operator(c @ (';' | '}'))) = parser.tokens.peek().copied() {
+                // The token ends the line: leave it for error recovery to
+                // resume from.
+                let span = parser.tokens.peek_span();
+                let error = CompileError::parse(format!("Expected expression but found operator: {}", c), span);
+                return Err(error.with_code(ErrorCode::UnexpectedToken));
             } else {
-                match parser
-                    .tokens
-                    .next()
-                    .ok_or("Expected expression but found end of input")??
-                {
-                    Number(n) => Ok(Literal(n.parse().map_err(|e| format!("Failed to parse number: {}", e))?)),
-                    Symbol(s) => Ok(Variable(s)),
-                    Operator('(') => {
-                        let inside = Expression::parse(parser)?;
-                        parser.tokens.expect_operator_of(')')?;
+                match parser.tokens.next() {
+                    Some(Number) => {
+                        let value = parser.tokens.text().parse().map_err(|e| {
+                            CompileError::parse(format!("Failed to parse number: {}", e), parser.tokens.span())
+                                .with_code(ErrorCode::InvalidNumber)
+                        })?;
+                        Ok(Literal(value, parser.tokens.span()))
+                    },
+                    Some(Symbol(s)) => Ok(Variable(parser.tokens.resolve(s).to_string(), parser.tokens.span())),
+                    Some(Keyword(k)) => Err(parser.tokens.reserved_keyword_error(k)),
+                    Some(Operator('(')) => {
+                        let open = parser.tokens.span();
+                        let inside = parser.nested(open, Expression::parse)?;
+                        parser.tokens.expect_operator_of(')').map_err(|e| {
+                            let e = e.with_label(open, "unclosed parenthesis");
+                            match e.span == Some(parser.tokens.end_span()) {
+                                true => e.with_code(ErrorCode::UnclosedDelimiter),
+                                false => e,
+                            }
+                        })?;
                         Ok(inside)
                     }
-                    x => Err(format!("Expected number or symbol but found {}", x)),
+                    token => Err(parser.tokens.unexpected(token, "expression")),
                 }?
             }
         } else {
             Expression::parse_prec(parser, prec - 1)?
         };
+        let left = if prec == 0 { Expression::parse_casts(parser, left)? } else { left };
+
+        // Read the whole chain of operators of this precedence in a loop,
+        // so that long chains don't recurse, then group it to the right.
+        let mut operands = vec![left];
+        let mut operators = Vec::new();
+        while let Some(Operator(c)) = parser.tokens.peek() {
+            if Expression::precedence(*c) > prec {
+                break;
+            }
+            let operator = parser.tokens.expect_operator()?;
+            operators.push((operator, parser.tokens.span()));
+            operands.push(Expression::parse_prec(parser, prec - 1)?);
+        }
 
-        match parser.tokens.peek() {
-            None => return Ok(left),
-            Some(Err(err)) => return Err(err.clone()),
-            Some(Ok(Operator(c))) if Expression::precedence(*c) <= prec => (),
-            Some(Ok(_)) => return Ok(left),
+        let mut right = operands.pop().expect("a chain has an operand");
+        while let Some((operator, operator_span)) = operators.pop() {
+            let left = Box::new(operands.pop().expect("an operator has a left operand"));
+            right = match operator {
+                '*' => Mul(left, Box::new(right), operator_span),
+                '/' => Div(left, Box::new(right), operator_span),
+                '+' => Add(left, Box::new(right), operator_span),
+                '-' => Sub(left, Box::new(right), operator_span),
+                x => return Err(CompileError::parse(format!("Expected valid operator but found {}", x), operator_span)
+                    .with_code(ErrorCode::UnexpectedToken)),
+            };
         }
+        Ok(right)
+    }
+}
 
-        let operator = match parser
-            .tokens
-            .next()
-            .ok_or("Expected operator but found end of input")??
-        {
-            Operator(c) => Ok(c),
-            x => Err(format!("Expected operator but found {}", x)),
-        }?;
-        let right = Expression::parse_prec(parser, prec)?;
-        match operator {
-            '*' => Ok(Mul(Box::new(left), Box::new(right))),
-            '/' => Ok(Div(Box::new(left), Box::new(right))),
-            '+' => Ok(Add(Box::new(left), Box::new(right))),
-            '-' => Ok(Sub(Box::new(left), Box::new(right))),
-            x => Err(format!("Expected valid operator but found {}", x)),
+impl Expression {
+    /// Parses the `as type` following an operand, if any.
+    fn parse_casts(parser: &mut Parser, mut value: Expression) -> Result<Expression, CompileError> {
+        while parser.tokens.peek() == Some(&Token::Keyword(K::As)) {
+            parser.tokens.next();
+            let start = parser.tokens.span();
+            let type_ = parser.tokens.expect_symbol()?;
+            value = Expression::Cast(Box::new(value), type_, start.to(parser.tokens.span()));
         }
+        Ok(value)
     }
 }
 
 impl Parsable for Expression {
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
+    fn parse(parser: &mut Parser) -> Result<Self, CompileError> {
         Expression::parse_prec(parser, 3)
     }
 }
@@ -89,22 +209,24 @@ impl Parsable for Expression {
 #[cfg(test)]
 mod tests {
     use crate::block::Line;
+    use crate::error::ErrorKind;
     use crate::parser::Ast;
+    use crate::span::Span;
     use std::fs::File;
     use std::io::Write;
     use std::env::temp_dir;
 
     use super::*;
 
-    fn test(data: &'static str) -> Option<Result<Ast, String>> {
-        let mut parser = Parser::try_from(data).expect("Failed to create parser");
+    fn test(data: &str) -> Option<Result<Ast, Vec<CompileError>>> {
+        let mut parser = Parser::from(data);
         parser.next()
     }
 
     #[test]
     fn test_parse_literal() {
         match test("42").unwrap().unwrap() {
-            Ast::Expression(Expression::Literal(42.)) => (),
+            Ast::Expression(Expression::Literal(42., _)) => (),
             x => panic!("Expected literal 42 ; got {:?}", x),
         }
     }
@@ -112,7 +234,7 @@ mod tests {
     #[test]
     fn test_parse_variable() {
         match test("x").unwrap().unwrap() {
-            Ast::Expression(Expression::Variable(ref s)) if s == "x" => (),
+            Ast::Expression(Expression::Variable(ref s, _)) if s == "x" => (),
             x => panic!("Expected variable x ; got {:?}", x),
         }
     }
@@ -121,8 +243,9 @@ mod tests {
     fn test_parse_addition() {
         match test("1 + 2").unwrap().unwrap() {
             Ast::Expression(Expression::Add(
-                box Expression::Literal(1.),
-                box Expression::Literal(2.),
+                box Expression::Literal(1., _),
+                box Expression::Literal(2., _),
+                _,
             )) => (),
             x => panic!("Expected addition of 1 and 2 ; got {:?}", x),
         }
@@ -132,8 +255,9 @@ mod tests {
     fn test_parse_subtraction() {
         match test("3 - 1").unwrap().unwrap() {
             Ast::Expression(Expression::Sub(
-                box Expression::Literal(3.),
-                box Expression::Literal(1.),
+                box Expression::Literal(3., _),
+                box Expression::Literal(1., _),
+                _,
             )) => (),
             x => panic!("Expected subtraction of 3 and 1 ; got {:?}", x),
         }
@@ -143,8 +267,9 @@ mod tests {
     fn test_parse_multiplication() {
         match test("4 * 2").unwrap().unwrap() {
             Ast::Expression(Expression::Mul(
-                box Expression::Literal(4.),
-                box Expression::Literal(2.),
+                box Expression::Literal(4., _),
+                box Expression::Literal(2., _),
+                _,
             )) => (),
             x => panic!("Expected multiplication of 4 and 2 ; got {:?}", x),
         }
@@ -154,8 +279,9 @@ mod tests {
     fn test_parse_division() {
         match test("8 / 4").unwrap().unwrap() {
             Ast::Expression(Expression::Div(
-                box Expression::Literal(8.),
-                box Expression::Literal(4.),
+                box Expression::Literal(8., _),
+                box Expression::Literal(4., _),
+                _,
             )) => (),
             x => panic!("Expected division of 8 by 4 ; got {:?}", x),
         }
@@ -165,8 +291,9 @@ mod tests {
     fn test_parse_complex_expression() {
         match test("1 + 2 * 3").unwrap().unwrap() {
             Ast::Expression(Expression::Add(
-                box Expression::Literal(1.),
-                box Expression::Mul(box Expression::Literal(2.), box Expression::Literal(3.)),
+                box Expression::Literal(1., _),
+                box Expression::Mul(box Expression::Literal(2., _), box Expression::Literal(3., _), _),
+                _,
             )) => (),
             x => panic!("Expected complex expression 1 + 2 * 3 ; got {:?}", x),
         }
@@ -176,8 +303,9 @@ mod tests {
     fn test_parse_precedence() {
         match test("1 * 2 + 3").unwrap().unwrap() {
             Ast::Expression(Expression::Add(
-                box Expression::Mul(box Expression::Literal(1.), box Expression::Literal(2.)),
-                box Expression::Literal(3.),
+                box Expression::Mul(box Expression::Literal(1., _), box Expression::Literal(2., _), _),
+                box Expression::Literal(3., _),
+                _,
             )) => (),
             x => panic!("Expected complex expression 1 * 2 + 3 ; got {:?}", x),
         }
@@ -187,19 +315,37 @@ mod tests {
     fn test_parse_big() {
         match test("1 + 2 * 3 * 4 + 5").unwrap().unwrap() {
             Ast::Expression(Expression::Add(
-                box Expression::Literal(1.),
+                box Expression::Literal(1., _),
                 box Expression::Add(
                     box Expression::Mul(
-                        box Expression::Literal(2.),
-                        box Expression::Mul(box Expression::Literal(3.), box Expression::Literal(4.)),
+                        box Expression::Literal(2., _),
+                        box Expression::Mul(box Expression::Literal(3., _), box Expression::Literal(4., _), _),
+                        _,
                     ),
-                    box Expression::Literal(5.),
-                )
+                    box Expression::Literal(5., _),
+                    _,
+                ),
+                _,
             )) => (),
             x => panic!("Expected complex expression 1 + 2 * 3 * 4 + 5 ; got {:?}", x),
         }
     }
 
+    #[test]
+    fn test_parse_cast() {
+        match test("1 + 2 as i64 as f64").unwrap().unwrap() {
+            Ast::Expression(Expression::Add(
+                box Expression::Literal(1., _),
+                box Expression::Cast(box Expression::Cast(box Expression::Literal(2., _), inner, _), outer, span),
+                _,
+            )) => {
+                assert_eq!((inner.as_str(), outer.as_str()), ("i64", "f64"));
+                assert_eq!(span, Span::new(13, 19));
+            },
+            x => panic!("Expected conversions of 2 ; got {:?}", x),
+        }
+    }
+
     #[test]
     fn test_on_file() {
         let data = "1 + 2   ";
@@ -213,8 +359,9 @@ mod tests {
         let ast = parser.next().unwrap().unwrap();
         match ast {
             Ast::Expression(Expression::Add(
-                box Expression::Literal(1.),
-                box Expression::Literal(2.),
+                box Expression::Literal(1., _),
+                box Expression::Literal(2., _),
+                _,
             )) => (),
             x => panic!("Expected addition of 1 and 2 ; got {:?}", x),
         }
@@ -225,10 +372,12 @@ mod tests {
         match test("(1 + 2 ) * 3").unwrap().unwrap() {
             Ast::Expression(Expression::Mul(
                 box Expression::Add(
-                    box Expression::Literal(1.),
-                    box Expression::Literal(2.),
+                    box Expression::Literal(1., _),
+                    box Expression::Literal(2., _),
+                    _,
                 ),
-                box Expression::Literal(3.),
+                box Expression::Literal(3., _),
+                _,
             )) => (),
             x => panic!("Expected parantheses (1 + 2) * 3 ; got {:?}", x),
         }
@@ -238,11 +387,13 @@ mod tests {
     fn test_parantheses2() {
         match test("1 * (2 + 3)").unwrap().unwrap() {
             Ast::Expression(Expression::Mul(
-                box Expression::Literal(1.),
+                box Expression::Literal(1., _),
                 box Expression::Add(
-                    box Expression::Literal(2.),
-                    box Expression::Literal(3.),
+                    box Expression::Literal(2., _),
+                    box Expression::Literal(3., _),
+                    _,
                 ),
+                _,
             )) => (),
             x => panic!("Expected parantheses 1 * (2 + 3) ; got {:?}", x),
         }
@@ -253,13 +404,16 @@ mod tests {
         match test("(1 * 2) + (3 * 4)").unwrap().unwrap() {
             Ast::Expression(Expression::Add(
                 box Expression::Mul(
-                    box Expression::Literal(1.),
-                    box Expression::Literal(2.),
+                    box Expression::Literal(1., _),
+                    box Expression::Literal(2., _),
+                    _,
                 ),
                 box Expression::Mul(
-                    box Expression::Literal(3.),
-                    box Expression::Literal(4.),
+                    box Expression::Literal(3., _),
+                    box Expression::Literal(4., _),
+                    _,
                 ),
+                _,
             )) => (),
             x => panic!("Expected parantheses (1 * 2) + (3 * 4) ; got {:?}", x),
         }
@@ -269,14 +423,15 @@ mod tests {
     fn test_block() {
         match test("12 + { 30 }").unwrap().unwrap() {
             Ast::Expression(Expression::Add(
-                box Expression::Literal(12.),
+                box Expression::Literal(12., _),
                 box Expression::Block(block),
+                _,
             )) => {
                 assert!(!block.lines.is_empty());
                 assert!(block.has_value());
                 match &block.lines[0] {
                     Line::ReturnStatement(expr) => match expr {
-                        Expression::Literal(30.) => (),
+                        Expression::Literal(30., _) => (),
                         x => panic!("Expected block 12 + {{ 30 }} ; got {:?}", x),
                     },
                     x => panic!("Expected block 12 + {{ 30 }} ; got {:?}", x),
@@ -293,4 +448,43 @@ mod tests {
             x => panic!("Expected error ; got {:?}", x),
         }
     }
+
+    #[test]
+    fn test_lexical_errors() {
+        let errors = Parser::from("1 § 2 +\n 🙂 3")
+            .filter_map(Result::err)
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 2, "unexpected errors: {:?}", errors);
+        assert!(errors.iter().all(|e| e.kind == ErrorKind::Lex));
+        assert_eq!(errors[0].span, Some(Span::new(2, 4)));
+        assert_eq!(errors[1].span, Some(Span::new(10, 14)));
+    }
+
+    #[test]
+    fn test_error_spans() {
+        match test("1 + (2 * 3") {
+            Some(Err(e)) => {
+                assert_eq!(e[0].kind, ErrorKind::Parse);
+                assert_eq!(e[0].span, Some(Span::new(10, 10)));
+                assert_eq!(e[0].labels[0].span, Span::new(4, 5));
+            },
+            x => panic!("Expected error ; got {:?}", x),
+        }
+        match test("{ 30; } + 12") {
+            Some(Err(e)) => {
+                assert_eq!(e[0].kind, ErrorKind::Type);
+                assert_eq!(e[0].span, Some(Span::new(0, 7)));
+            },
+            x => panic!("Expected error ; got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_keyword_as_variable() {
+        match test("1 + if") {
+            Some(Err(e)) => assert!(e[0].notes[0].contains("reserved"), "unexpected error: {:?}", e),
+            x => panic!("Expected error ; got {:?}", x),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,74 @@
+use crate::error::{Applicability, Suggestion};
+
+/// Applies the machine-applicable suggestions to `text`, returning the
+/// fixed text and how many were applied. Suggestions overlapping one applied
+/// before are skipped; they can be applied by fixing the text again.
+pub fn apply<'s>(text: &str, suggestions: impl IntoIterator<Item = &'s Suggestion>) -> (String, usize) {
+    let mut suggestions = suggestions
+        .into_iter()
+        .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+        .collect::<Vec<_>>();
+    suggestions.sort_by_key(|suggestion| (suggestion.span.start, suggestion.span.end));
+    let mut fixed = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut applied = 0;
+    // Where the last insertion applied was, if it was the last edit.
+    let mut inserted = None;
+    for suggestion in suggestions {
+        let span = suggestion.span;
+        // An insertion right where a replacement ended is fine, but not one
+        // at the same place as a previous insertion.
+        let overlaps = span.start < copied || (span.is_empty() && inserted == Some(span.start));
+        if overlaps || span.end > text.len() {
+            continue;
+        }
+        fixed.push_str(&text[copied..span.start]);
+        fixed.push_str(&suggestion.replacement);
+        copied = span.end;
+        inserted = span.is_empty().then_some(span.start);
+        applied += 1;
+    }
+    fixed.push_str(&text[copied..]);
+    (fixed, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    fn suggestion(start: usize, end: usize, replacement: &str, applicability: Applicability) -> Suggestion {
+        let span = Span::new(start, end);
+        Suggestion { message: String::new(), span, replacement: replacement.to_string(), applicability }
+    }
+
+    #[test]
+    fn test_apply() {
+        let suggestions = [
+            suggestion(11, 11, ";", Applicability::MachineApplicable),
+            suggestion(8, 9, ":=", Applicability::MachineApplicable),
+            suggestion(2, 5, "return", Applicability::MaybeIncorrect),
+        ];
+        let (fixed, applied) = apply("{ let x = 1\n  let y := x; y }", &suggestions);
+        assert_eq!(applied, 2);
+        assert_eq!(fixed, "{ let x := 1;\n  let y := x; y }");
+    }
+
+    #[test]
+    fn test_skip_overlapping() {
+        let suggestions = [
+            suggestion(0, 3, "abc", Applicability::MachineApplicable),
+            suggestion(2, 4, "xy", Applicability::MachineApplicable),
+            suggestion(4, 4, ";", Applicability::MachineApplicable),
+            suggestion(4, 4, ";", Applicability::MachineApplicable),
+        ];
+        assert_eq!(apply("0123", &suggestions), ("abc3;".to_string(), 2));
+        // An insertion right after a replacement.
+        let suggestions = [
+            suggestion(0, 3, "abc", Applicability::MachineApplicable),
+            suggestion(3, 3, ";", Applicability::MachineApplicable),
+            suggestion(3, 3, ";", Applicability::MachineApplicable),
+        ];
+        assert_eq!(apply("0123", &suggestions), ("abc;3".to_string(), 2));
+    }
+}
@@ -0,0 +1,290 @@
+//! Compile-time evaluation of constant expressions, before code generation.
+//!
+//! Subtrees whose operands are all constants are replaced by their value,
+//! including variables bound by `let` to a constant and blocks whose lines
+//! are all constant. Operations with one constant operand are simplified
+//! with the identities which hold for every value of the other one; for
+//! `f64` that includes -0 and NaN, so `x * 0` and `x + 0` are kept. Constant
+//! operations which fail the runtime checks are kept as they are, to fail
+//! at runtime, and reported by the `unconditional_fault` lint.
+
+use std::collections::HashMap;
+
+use crate::block::{Block, Line};
+use crate::expression::Expression;
+use crate::parser::Ast;
+use crate::span::Span;
+use crate::testing::Test;
+use crate::typeck::Type;
+use crate::value::{Fault, Value};
+
+/// Folds the constant expressions of a program which passed the front-end.
+pub fn fold(program: &[Ast]) -> Vec<Ast> {
+    let mut folder = Folder::new();
+    program.iter().map(|ast| folder.ast(ast)).collect()
+}
+
+/// Folds an entry of the REPL, keeping its `let` statements for the next
+/// entries. The variables of the previous entries are not constants.
+pub fn fold_entry(block: &Block) -> Block {
+    let mut folder = Folder::new();
+    let (lines, _) = folder.lines(&block.lines);
+    Block { lines, info: block.info.clone(), attributes: block.attributes.clone(), span: block.span }
+}
+
+/// The operations of a program which fail whenever they run, by the span
+/// of their operator or `as`. Code after a `return` never runs.
+pub fn faults(program: &[Ast]) -> Vec<(Span, Fault)> {
+    let mut folder = Folder::new();
+    for ast in program {
+        folder.ast(ast);
+    }
+    folder.faults
+}
+
+/// A folded expression and its value, if it is a constant.
+type Folded = (Expression, Option<Value>);
+
+struct Folder {
+    /// The value of the variables of each block, innermost last, or `None`
+    /// for those which are not constants.
+    scopes: Vec<HashMap<String, Option<Value>>>,
+    faults: Vec<(Span, Fault)>,
+}
+
+impl Folder {
+    fn new() -> Self {
+        Folder { scopes: vec![HashMap::new()], faults: Vec::new() }
+    }
+
+    fn ast(&mut self, ast: &Ast) -> Ast {
+        match ast {
+            Ast::Expression(expression) => Ast::Expression(self.expression(expression).0),
+            // The body of a test is kept even if it is constant, for its asserts.
+            Ast::Test(test) => {
+                self.scopes.push(HashMap::new());
+                let (lines, _) = self.lines(&test.body.lines);
+                self.scopes.pop();
+                let body = Block { lines, ..test.body.clone() };
+                Ast::Test(Test { body, ..test.clone() })
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Folded {
+        match expression {
+            Expression::Literal(x, _) => (expression.clone(), Some(Value::F64(*x))),
+            Expression::Variable(name, span) => match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+                Some(Some(value)) => constant(*value, *span, expression.clone()),
+                _ => (expression.clone(), None),
+            },
+            Expression::Block(block) => self.block(block),
+            Expression::Add(a, b, span) => self.binary('+', a, b, *span),
+            Expression::Sub(a, b, span) => self.binary('-', a, b, *span),
+            Expression::Mul(a, b, span) => self.binary('*', a, b, *span),
+            Expression::Div(a, b, span) => self.binary('/', a, b, *span),
+            Expression::Cast(value, name, span) => {
+                let (value, x) = self.expression(value);
+                let cast = Expression::Cast(Box::new(value), name.clone(), *span);
+                match (x, Type::lookup(name)) {
+                    (Some(x), Some(type_)) => match x.convert(type_) {
+                        Ok(value) => constant(value, *span, cast),
+                        Err(fault) => {
+                            self.faults.push((*span, fault));
+                            (cast, None)
+                        },
+                    },
+                    _ => (cast, None),
+                }
+            },
+        }
+    }
+
+    fn binary(&mut self, operator: char, a: &Expression, b: &Expression, span: Span) -> Folded {
+        let ((a, x), (b, y)) = (self.expression(a), self.expression(b));
+        match (x, y) {
+            (Some(x), Some(y)) if x.type_() == y.type_() => match Value::binary(operator, x, y) {
+                Ok(value) => constant(value, span, operation(operator, a, b, span)),
+                Err(fault) => {
+                    self.faults.push((span, fault));
+                    (operation(operator, a, b, span), None)
+                },
+            },
+            (None, Some(y)) if is_right_identity(operator, y) => (a, None),
+            (Some(x), None) if is_left_identity(operator, x) => (b, None),
+            _ => (operation(operator, a, b, span), None),
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> Folded {
+        self.scopes.push(HashMap::new());
+        let (lines, value) = self.lines(&block.lines);
+        self.scopes.pop();
+        let folded = Block { lines, info: block.info.clone(), attributes: block.attributes.clone(), span: block.span };
+        match value {
+            Some(value) => constant(value, block.span, Expression::Block(folded)),
+            None => (Expression::Block(folded), None),
+        }
+    }
+
+    /// Folds the lines up to the first `return`, which are the ones which
+    /// run, and returns the value it returns if every one of them is
+    /// constant.
+    fn lines(&mut self, lines: &[Line]) -> (Vec<Line>, Option<Value>) {
+        let mut folded = Vec::with_capacity(lines.len());
+        let mut constant = true;
+        for (i, line) in lines.iter().enumerate() {
+            match line {
+                Line::Expression(expression) => {
+                    let (expression, value) = self.expression(expression);
+                    constant &= value.is_some();
+                    folded.push(Line::Expression(expression));
+                },
+                Line::LetStatement { name, span, value, type_ } => {
+                    let (value, x) = self.expression(value);
+                    constant &= x.is_some();
+                    self.scopes.last_mut().expect("a block has a scope").insert(name.clone(), x);
+                    folded.push(Line::LetStatement { name: name.clone(), span: *span, value, type_: type_.clone() });
+                },
+                Line::ReturnStatement(expression) => {
+                    let (expression, value) = self.expression(expression);
+                    folded.push(Line::ReturnStatement(expression));
+                    folded.extend_from_slice(&lines[i + 1..]);
+                    return (folded, value.filter(|_| constant));
+                },
+                Line::Assert { left, comparison, right, span } => {
+                    let ((left, x), (right, y)) = (self.expression(left), self.expression(right));
+                    constant &= match (x, y) {
+                        (Some(Value::F64(x)), Some(Value::F64(y))) => comparison.holds(x, y),
+                        (Some(Value::I64(x)), Some(Value::I64(y))) => comparison.holds(x, y),
+                        _ => false,
+                    };
+                    folded.push(Line::Assert { left, comparison: *comparison, right, span: *span });
+                },
+                Line::Error(span) => {
+                    constant = false;
+                    folded.push(Line::Error(*span));
+                },
+            }
+        }
+        (folded, None)
+    }
+}
+
+/// A constant written as a literal, or `otherwise` for the values no
+/// literal can write: negative numbers, -0, NaN and infinities, and the
+/// `i64` values which are not written `x as i64` with an exact `f64`
+/// literal.
+fn constant(value: Value, span: Span, otherwise: Expression) -> Folded {
+    let literal = match value {
+        Value::F64(x) if x.is_finite() && x.is_sign_positive() => Expression::Literal(x, span),
+        Value::I64(x) if x >= 0 && Value::F64(x as f64).convert(Type::I64) == Ok(value) => {
+            Expression::Cast(Box::new(Expression::Literal(x as f64, span)), Type::I64.to_string(), span)
+        },
+        Value::F64(_) | Value::I64(_) => otherwise,
+    };
+    (literal, Some(value))
+}
+
+fn operation(operator: char, a: Expression, b: Expression, span: Span) -> Expression {
+    let (a, b) = (Box::new(a), Box::new(b));
+    match operator {
+        '+' => Expression::Add(a, b, span),
+        '-' => Expression::Sub(a, b, span),
+        '*' => Expression::Mul(a, b, span),
+        _ => Expression::Div(a, b, span),
+    }
+}
+
+/// Whether `x <operator> y` is `x` whatever `x` is. `x + 0.` is not for
+/// `x = -0.`, but `x + -0.` and `x - 0.` are.
+fn is_right_identity(operator: char, y: Value) -> bool {
+    match (operator, y) {
+        ('+' | '-', Value::I64(0)) | ('*' | '/', Value::I64(1)) => true,
+        ('+', Value::F64(y)) => y == 0. && y.is_sign_negative(),
+        ('-', Value::F64(y)) => y == 0. && y.is_sign_positive(),
+        ('*' | '/', Value::F64(y)) => y == 1.,
+        _ => false,
+    }
+}
+
+/// Whether `x <operator> y` is `y` whatever `y` is.
+fn is_left_identity(operator: char, x: Value) -> bool {
+    match (operator, x) {
+        ('+', Value::I64(0)) | ('*', Value::I64(1)) => true,
+        ('+', Value::F64(x)) => x == 0. && x.is_sign_negative(),
+        ('*', Value::F64(x)) => x == 1.,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump;
+    use crate::interp::Interpreter;
+    use crate::parser::Parser;
+
+    fn program(data: &str) -> Vec<Ast> {
+        Parser::from(data).collect::<Result<Vec<_>, _>>().expect("test programs are valid")
+    }
+
+    fn folded(data: &str) -> Vec<String> {
+        fold(&program(data)).iter().map(dump::sexp_ast).collect()
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        assert_eq!(folded("30 + 12"), ["42"]);
+        assert_eq!(folded("{ let x := 2 as i64; let y := x * (3 as i64); y / (4 as i64) }"), ["(as 1 i64)"]);
+        assert_eq!(folded("{ let x := 1; { let x := 2; x } + x }"), ["3"]);
+        // Only the value of the first `return` matters.
+        assert_eq!(folded("{ return 1; 2 }"), ["1"]);
+        // Blocks with asserts are only constant when they hold.
+        assert_eq!(folded("{ assert(1 < 2); 1 + 1 }"), ["2"]);
+        assert_eq!(folded("{ assert(1 > 2); 1 + 1 }"), ["(block (assert (> 1 2)) (return 2))"]);
+        assert_eq!(folded("test \"t\" { assert(2 * 3 == 6) }"), ["(test \"t\" (assert (== 6 6)))"]);
+    }
+
+    #[test]
+    fn test_unrepresentable_integers() {
+        // `i64::MAX` is not written as a literal, but its value is known.
+        let max = "4611686018427387904 as i64 * (2 as i64) - 1 as i64";
+        assert_eq!(folded(max), ["(- (* (as 4611686018427388000 i64) (as 2 i64)) (as 1 i64))"]);
+        assert_eq!(folded(&format!("{{ let max := {}; max as f64 }}", max)), ["9223372036854776000"]);
+    }
+
+    #[test]
+    fn test_identities() {
+        let entry = |data: &str| {
+            let block = fold_entry(&Block::parse_entry(&mut Parser::from(data)));
+            dump::sexp(&Expression::Block(block))
+        };
+        assert_eq!(entry("(x * 1 - 0) + 2 * 0"), "(block (return (+ x 0)))");
+        assert_eq!(entry("(1 * (x - 0 / 1) / 1) * y"), "(block (return (* x y)))");
+        assert_eq!(entry("x * 0 + (0 - 0) * (0 - 1)"), "(block (return (* x 0)))");
+        assert_eq!(entry("x as i64 * (1 as i64) + 0 as i64 * y"), "(block (return (+ (as x i64) (* (as 0 i64) y))))");
+        assert_eq!(entry("0 as i64 + x - 0 as i64"), "(block (return x))");
+    }
+
+    #[test]
+    fn test_faults() {
+        let program = program("{ let zero := 0 as i64; let x := 1 as i64 / zero; return (0 / 0) as i64; 1 as i64 / zero }");
+        assert_eq!(faults(&program), [(Span::new(42, 43), Fault::DivisionByZero), (Span::new(65, 71), Fault::OutOfRange)]);
+        // Faulty operations are kept to fail at runtime.
+        assert_eq!(
+            dump::sexp_ast(&fold(&program)[0]),
+            "(block (let zero (as 0 i64)) (let x (/ (as 1 i64) (as 0 i64))) (return (as (/ 0 0) i64)) (return (/ (as 1 i64) zero)))",
+        );
+    }
+
+    #[test]
+    fn test_unwritable_constants() {
+        assert_eq!(folded("0 - 1 * 2"), ["(- 0 2)"]);
+        assert_eq!(folded("{ let x := 0 * (0 - 1); 1 / x }"), ["(block (let x (* 0 (- 0 1))) (return (/ 1 x)))"]);
+        // Converting NaN still fails at runtime.
+        let program = fold(&program("(0 / 0) as i64"));
+        let error = Interpreter::new().run(&program).unwrap_err();
+        assert_eq!(error.message, "out-of-range conversion to i64");
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::VecDeque;
+
+use crate::attribute::Attribute;
+use crate::block::{Block, Line, LineInfo};
+use crate::error::CompileError;
+use crate::expression::Expression;
+use crate::parser::{Ast, Parser};
+use crate::span::Span;
+
+const INDENT: &str = "    ";
+
+/// Formats a program: one line per statement, blocks indented, one space
+/// around operators and only the parentheses precedence requires. Comments
+/// are kept, on the line they were on when possible, as are single blank
+/// lines between statements. Programs with syntax errors are not
+/// formatted; their errors are returned instead.
+pub fn format(text: &str) -> Result<String, Vec<CompileError>> {
+    let mut parser = Parser::from(text);
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(item) = parser.next() {
+        match item {
+            Ok(ast) => items.push((ast, parser.span())),
+            Err(e) => errors.extend(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let comments = parser.tokens.comments().iter().copied().collect();
+    let mut formatter = Formatter { text, comments, out: String::new(), indent: 0 };
+    formatter.program(&parser.attributes, &items);
+    Ok(formatter.out)
+}
+
+struct Formatter<'a> {
+    text: &'a str,
+    /// Comments not written yet, in order.
+    comments: VecDeque<Span>,
+    out: String,
+    indent: usize,
+}
+
+impl Formatter<'_> {
+    fn program(&mut self, attributes: &[Attribute], items: &[(Ast, Span)]) {
+        let mut end = None;
+        for attribute in attributes {
+            self.separate(end, attribute.span.start);
+            self.leading(attribute.span.start);
+            self.attribute(attribute);
+            self.out.push('\n');
+            end = Some(attribute.span.end);
+        }
+        for (i, (ast, span)) in items.iter().enumerate() {
+            let next = items.get(i + 1).map_or(self.text.len(), |(_, span)| span.start);
+            self.separate(end, span.start);
+            self.leading(span.start);
+            self.write_indent();
+            match ast {
+                Ast::Expression(expression) => self.expression(expression),
+                Ast::Test(test) => {
+                    self.out.push_str("test \"");
+                    self.out.push_str(&test.name);
+                    self.out.push_str("\" ");
+                    self.block(&test.body);
+                },
+            }
+            self.trailing(span.end, next);
+            self.out.push('\n');
+            end = Some(span.end);
+        }
+        self.leading_before_end(end, self.text.len());
+    }
+
+    /// Writes a blank line before what starts at `start` if there is one
+    /// in the source, ignoring the comments before it.
+    fn separate(&mut self, end: Option<usize>, start: usize) {
+        let start = self.comments.front().map_or(start, |comment| comment.start.min(start));
+        if let Some(end) = end {
+            if self.text[end..start].matches('\n').count() > 1 {
+                self.out.push('\n');
+            }
+        }
+    }
+
+    /// Writes the comments after the last statement, which ends at `end`,
+    /// and before the end of what contains it at `close`.
+    fn leading_before_end(&mut self, end: Option<usize>, close: usize) {
+        if self.comments.front().is_some_and(|comment| comment.start < close) {
+            self.separate(end, close);
+            self.leading(close);
+        }
+    }
+
+    /// Writes the comments before `start` on lines of their own.
+    fn leading(&mut self, start: usize) {
+        while let Some(comment) = self.comments.front().filter(|comment| comment.start < start).copied() {
+            self.comments.pop_front();
+            self.write_indent();
+            self.out.push_str(self.text[comment.start..comment.end].trim_end());
+            self.out.push('\n');
+        }
+    }
+
+    /// Writes the comments left in a statement ending at `end` and the ones
+    /// after it on the same line, before `next`, after the statement.
+    fn trailing(&mut self, end: usize, next: usize) {
+        while let Some(comment) = self.comments.front().copied() {
+            let inside = comment.start < end;
+            if !inside && (comment.start >= next || self.text[end..comment.start].contains('\n')) {
+                break;
+            }
+            self.comments.pop_front();
+            self.out.push(' ');
+            self.out.push_str(self.text[comment.start..comment.end].trim_end());
+        }
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        self.out.push_str(if attribute.inner { "#![" } else { "#[" });
+        self.out.push_str(&attribute.name);
+        if !attribute.args.is_empty() {
+            let args = attribute.args.iter().map(|(arg, _)| arg.as_str()).collect::<Vec<_>>();
+            self.out.push('(');
+            self.out.push_str(&args.join(", "));
+            self.out.push(')');
+        }
+        self.out.push(']');
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.out.push_str("{\n");
+        self.indent += 1;
+        let mut end = None;
+        for attribute in &block.attributes {
+            self.separate(end, attribute.span.start);
+            self.leading(attribute.span.start);
+            self.write_indent();
+            self.attribute(attribute);
+            self.out.push('\n');
+            end = Some(attribute.span.end);
+        }
+        for (i, (line, info)) in block.lines.iter().zip(&block.info).enumerate() {
+            let next = block.info.get(i + 1).map_or(block.span.end, Formatter::start);
+            self.separate(end, Formatter::start(info));
+            self.leading(Formatter::start(info));
+            for attribute in &info.attributes {
+                self.write_indent();
+                self.attribute(attribute);
+                self.out.push('\n');
+            }
+            self.write_indent();
+            self.line(line, i + 1 == block.lines.len());
+            self.trailing(info.span.end, next);
+            self.out.push('\n');
+            end = Some(info.span.end);
+        }
+        // Comments before the closing brace.
+        self.leading_before_end(end, block.span.end.saturating_sub(1));
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    /// Where a line starts, including its attributes.
+    fn start(info: &LineInfo) -> usize {
+        info.attributes.first().map_or(info.span.start, |attribute| attribute.span.start)
+    }
+
+    /// Writes a line; the `return` ending a block is written as the value
+    /// of the block.
+    fn line(&mut self, line: &Line, last: bool) {
+        match line {
+            Line::Expression(expression) => {
+                self.expression(expression);
+                self.out.push(';');
+            },
+            Line::LetStatement { name, value, type_, .. } => {
+                self.out.push_str("let ");
+                self.out.push_str(name);
+                match type_ {
+                    Some((type_, _)) => {
+                        self.out.push_str(": ");
+                        self.out.push_str(type_);
+                        self.out.push_str(" = ");
+                    },
+                    None => self.out.push_str(" := "),
+                }
+                self.expression(value);
+                self.out.push(';');
+            },
+            Line::ReturnStatement(expression) if last => self.expression(expression),
+            Line::ReturnStatement(expression) => {
+                self.out.push_str("return ");
+                self.expression(expression);
+                self.out.push(';');
+            },
+            Line::Assert { left, comparison, right, .. } => {
+                self.out.push_str("assert(");
+                self.expression(left);
+                self.out.push(' ');
+                self.out.push_str(comparison.as_str());
+                self.out.push(' ');
+                self.expression(right);
+                self.out.push_str(");");
+            },
+            Line::Error(_) => unreachable!("programs with errors are not formatted"),
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Literal(_, span) => self.out.push_str(&self.text[span.start..span.end]),
+            Expression::Variable(name, _) => self.out.push_str(name),
+            Expression::Block(block) => self.block(block),
+            Expression::Add(a, b, _) | Expression::Sub(a, b, _) | Expression::Mul(a, b, _) | Expression::Div(a, b, _) => {
+                let operator = expression.operator().expect("binary operations have an operator");
+                let own = Expression::precedence(operator);
+                // Operators group to the right: `1 - 2 - 3` is `1 - (2 - 3)`,
+                // so a left operand of the same precedence needs parentheses.
+                self.operand(a, precedence(a) >= own);
+                self.out.push(' ');
+                self.out.push(operator);
+                self.out.push(' ');
+                self.operand(b, precedence(b) > own);
+            },
+            Expression::Cast(value, type_, _) => {
+                self.operand(value, precedence(value) > 0);
+                self.out.push_str(" as ");
+                self.out.push_str(type_);
+            },
+        }
+    }
+
+    fn operand(&mut self, expression: &Expression, parenthesized: bool) {
+        if parenthesized {
+            self.out.push('(');
+        }
+        self.expression(expression);
+        if parenthesized {
+            self.out.push(')');
+        }
+    }
+}
+
+/// The precedence of an expression, 0 for the ones which are not binary
+/// operations and never need parentheses.
+fn precedence(expression: &Expression) -> i8 {
+    expression.operator().map_or(0, Expression::precedence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_formats(text: &str, expected: &str) {
+        let formatted = format(text).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted).unwrap(), formatted, "formatting is not idempotent");
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        assert_formats(
+            "{let x:=1;let y : i64=x as i64;  return 2;{ x }*  y as f64}",
+            "{\n    let x := 1;\n    let y: i64 = x as i64;\n    return 2;\n    {\n        x\n    } * y as f64\n}\n",
+        );
+        assert_formats("{ return 1; }", "{\n    1\n}\n");
+        assert_formats("", "");
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        assert_formats("((1 + 2)) * (3 * 4) - (5 - (6 / 7))", "(1 + 2) * 3 * 4 - 5 - 6 / 7\n");
+        assert_formats("(1 - 2) - (3 + 4)", "(1 - 2) - 3 + 4\n");
+        assert_formats("(8 / 4) / 2 + 8 / (4 / 2)", "(8 / 4) / 2 + 8 / 4 / 2\n");
+        assert_formats("(1 + 2) as i64 + (3 as f64)", "(1 + 2) as i64 + 3 as f64\n");
+    }
+
+    #[test]
+    fn test_format_literals() {
+        // Numbers are written as they are, even those an f64 rounds.
+        assert_formats("9007199254740993+00100000000000000000000000001", "9007199254740993 + 00100000000000000000000000001\n");
+    }
+
+    #[test]
+    fn test_format_comments() {
+        assert_formats(
+            "// the answer\n{ // start\n  let x := 1; // one\n\n\n  // then\n  x + // two\n  41\n  // end\n} // done\n// bye",
+            "// the answer\n{\n    // start\n    let x := 1; // one\n\n    // then\n    x + 41 // two\n    // end\n} // done\n// bye\n",
+        );
+    }
+
+    #[test]
+    fn test_format_attributes() {
+        assert_formats(
+            "#![allow(unused_variables,  division_by_zero)]\n{ #![deny(unused_variables)] #[allow(division_by_zero)] 1 / 0 }",
+            "#![allow(unused_variables, division_by_zero)]\n{\n    #![deny(unused_variables)]\n    #[allow(division_by_zero)]\n    1 / 0\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_format_tests() {
+        assert_formats(
+            "1\ntest  \"one plus one\"{assert((1+1)==2)}",
+            "1\ntest \"one plus one\" {\n    assert(1 + 1 == 2);\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert!(format("{ 1 + }").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::attribute::Attribute;
+use crate::block::Block;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::{CompileError, Suggestion};
+use crate::expression::Expression;
+use crate::lint::{self, Level, Lint, LintLevels};
+use crate::parser::{Ast, Parser};
+use crate::typeck::Type;
+use crate::{resolve, typeck};
+
+/// A program parsed and checked, with everything found wrong with it.
+pub struct Checked {
+    pub program: Vec<Ast>,
+    /// `#![...]` attributes of the file.
+    pub attributes: Vec<Attribute>,
+    /// Errors which prevent compiling the program.
+    pub errors: Vec<CompileError>,
+    /// Warnings, and errors for denied lints. Lints are only checked on
+    /// programs without errors.
+    pub lints: Vec<Diagnostic>,
+}
+
+impl Checked {
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty() || self.lints.iter().any(|lint| lint.severity == Severity::Error)
+    }
+
+    pub fn diagnostics(&self) -> impl Iterator<Item = Diagnostic> + '_ {
+        self.errors.iter().map(Diagnostic::from).chain(self.lints.iter().cloned())
+    }
+
+    pub fn suggestions(&self) -> impl Iterator<Item = &Suggestion> {
+        let errors = self.errors.iter().flat_map(|error| &error.suggestions);
+        errors.chain(self.lints.iter().flat_map(|lint| &lint.suggestions))
+    }
+}
+
+/// Runs every stage of the compiler before code generation on a program.
+pub fn check(text: &str, levels: &LintLevels) -> Checked {
+    let mut parser = Parser::from(text);
+    let mut program = Vec::new();
+    let mut errors = Vec::new();
+    for item in parser.by_ref() {
+        match item {
+            Ok(ast) => program.push(ast),
+            Err(e) => errors.extend(e),
+        }
+    }
+    errors.extend(resolve::check(&program));
+    errors.extend(typeck::check(&program));
+    let lints = match errors.is_empty() {
+        true => lint::check(&program, &parser.attributes, levels),
+        false => Vec::new(),
+    };
+    Checked { program, attributes: parser.attributes, errors, lints }
+}
+
+/// An entry of the REPL, checked with the variables of the previous entries
+/// in scope.
+pub struct CheckedEntry {
+    pub block: Block,
+    /// Types of the variables in scope after the entry.
+    pub variables: HashMap<String, Type>,
+    /// The type of the value of the entry, if it has one.
+    pub type_: Option<Type>,
+    pub errors: Vec<CompileError>,
+    pub lints: Vec<Diagnostic>,
+}
+
+/// Runs every stage of the compiler before code generation on an entry of
+/// the REPL.
+pub fn check_entry(text: &str, levels: &LintLevels, variables: &HashMap<String, Type>) -> CheckedEntry {
+    let mut parser = Parser::from(text);
+    let block = Block::parse_entry(&mut parser);
+    let mut errors = parser.take_errors();
+    errors.extend(resolve::check_entry(&block, variables.keys().cloned()));
+    let mut variables = variables.clone();
+    let type_ = typeck::check_entry(&block, &mut variables).unwrap_or_else(|e| {
+        errors.extend(e);
+        None
+    });
+    let lints = match errors.is_empty() {
+        true => {
+            // The variables of an entry are there for the next ones.
+            let mut levels = levels.clone();
+            levels.set(Lint::UnusedVariables, Level::Allow);
+            lint::check(&[Ast::Expression(Expression::Block(block.clone()))], &[], &levels)
+        },
+        false => Vec::new(),
+    };
+    CheckedEntry { block, variables, type_, errors, lints }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// An interned identifier. Comparing two symbols is comparing their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Storage of identifier names, so that every distinct name is allocated
+/// only once however many times it appears in the source.
+#[derive(Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    names: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        let name: Rc<str> = name.into();
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_same_name() {
+        let mut interner = Interner::default();
+        let a = interner.intern("abc");
+        let b = interner.intern("xyz");
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("abc"), a);
+        assert_eq!(interner.resolve(a), "abc");
+        assert_eq!(interner.resolve(b), "xyz");
+    }
+}
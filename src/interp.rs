@@ -0,0 +1,220 @@
+//! A tree-walking interpreter with the semantics of the code `codegen`
+//! generates, for running programs without LLVM and checking the JIT.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::block::{Block, Line};
+use crate::code::ErrorCode;
+use crate::error::{CompileError, ErrorKind};
+use crate::expression::{Comparison, Expression};
+use crate::parser::Ast;
+use crate::source::SourceFile;
+use crate::span::Span;
+use crate::testing;
+use crate::typeck::Type;
+use crate::value::{Fault, Value};
+
+pub struct Interpreter {
+    /// Values of the variables of each block being run, innermost last.
+    scopes: Vec<HashMap<String, Value>>,
+    /// The program being run, to tell where runtime checks fail.
+    source: Option<SourceFile>,
+    /// Whether integer operations are checked, as with `CodeGen::with_checks`.
+    checks: bool,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter { scopes: Vec::new(), source: None, checks: true }
+    }
+
+    pub fn with_source(mut self, source: &SourceFile) -> Self {
+        self.source = Some(source.clone());
+        self
+    }
+
+    /// Disables the runtime checks of integer operations. What the JIT
+    /// leaves undefined is then defined as Rust does it: divisions wrap and
+    /// conversions saturate, except that division by zero still fails.
+    pub fn with_checks(mut self, checks: bool) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Runs the items of a program and returns the value of the last one
+    /// as an `f64`, as the function `CodeGen::compile_main` compiles does.
+    /// Failed runtime checks are errors of kind `ErrorKind::Runtime`. Tests
+    /// are not run.
+    pub fn run(&mut self, program: &[Ast]) -> Result<f64, CompileError> {
+        let mut value = 0.;
+        for ast in program {
+            let Ast::Expression(expression) = ast else { continue };
+            value = match self.expression(expression)? {
+                Value::F64(value) => value,
+                Value::I64(value) => value as f64,
+            };
+        }
+        Ok(value)
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Result<Value, CompileError> {
+        match expression {
+            Expression::Literal(x, _) => Ok(Value::F64(*x)),
+            Expression::Variable(name, span) => match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+                Some(value) => Ok(*value),
+                None => {
+                    let error = CompileError::resolve(format!("Cannot find variable '{}' in this scope", name), *span);
+                    Err(error.with_code(ErrorCode::UnknownVariable))
+                },
+            },
+            Expression::Add(a, b, span) => self.arithmetic('+', a, b, *span),
+            Expression::Sub(a, b, span) => self.arithmetic('-', a, b, *span),
+            Expression::Mul(a, b, span) => self.arithmetic('*', a, b, *span),
+            Expression::Div(a, b, span) => self.arithmetic('/', a, b, *span),
+            Expression::Cast(value, type_, span) => {
+                let type_ = Type::lookup(type_).ok_or_else(|| {
+                    CompileError::type_(format!("Cannot find type '{}'", type_), *span).with_code(ErrorCode::UnknownType)
+                })?;
+                let value = self.expression(value)?;
+                self.convert(value, type_, *span)
+            },
+            Expression::Block(block) => self.block(block),
+        }
+    }
+
+    /// The value of a block is the one of its first `return`; the lines
+    /// after it are not run.
+    fn block(&mut self, block: &Block) -> Result<Value, CompileError> {
+        self.scopes.push(HashMap::new());
+        let value = self.lines(&block.lines);
+        self.scopes.pop();
+        value?.ok_or_else(|| CompileError::codegen("Cannot run a block without return value"))
+    }
+
+    fn lines(&mut self, lines: &[Line]) -> Result<Option<Value>, CompileError> {
+        for line in lines {
+            match line {
+                Line::Expression(expression) => {
+                    self.expression(expression)?;
+                },
+                Line::LetStatement { name, value, .. } => {
+                    let value = self.expression(value)?;
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(name.clone(), value);
+                    }
+                },
+                Line::ReturnStatement(expression) => return self.expression(expression).map(Some),
+                Line::Assert { left, comparison, right, span } => self.assert(left, *comparison, right, *span)?,
+                Line::Error(_) => return Err(CompileError::codegen("Cannot run a line which failed to parse")),
+            }
+        }
+        Ok(None)
+    }
+
+    fn assert(&mut self, left: &Expression, comparison: Comparison, right: &Expression, span: Span) -> Result<(), CompileError> {
+        let holds = match (self.expression(left)?, self.expression(right)?) {
+            (Value::F64(x), Value::F64(y)) => comparison.holds(x, y),
+            (Value::I64(x), Value::I64(y)) => comparison.holds(x, y),
+            (x, y) => {
+                let message = format!("Cannot compare {} and {}", x.type_(), y.type_());
+                return Err(CompileError::type_(message, span).with_code(ErrorCode::MismatchedTypes));
+            },
+        };
+        match holds {
+            true => Ok(()),
+            false => Err(self.trap(testing::assertion_failed(self.source.as_ref(), span), span)),
+        }
+    }
+
+    fn arithmetic(&mut self, operator: char, a: &Expression, b: &Expression, span: Span) -> Result<Value, CompileError> {
+        let (x, y) = (self.expression(a)?, self.expression(b)?);
+        if x.type_() != y.type_() {
+            let message = format!("Cannot apply '{}' to {} and {}", operator, x.type_(), y.type_());
+            return Err(CompileError::type_(message, span).with_code(ErrorCode::MismatchedTypes));
+        }
+        match Value::binary(operator, x, y) {
+            Err(Fault::DivisionOverflow) if !self.checks => Ok(Value::I64(i64::MIN)),
+            result => result.map_err(|fault| self.trap(fault, span)),
+        }
+    }
+
+    fn convert(&self, value: Value, type_: Type, span: Span) -> Result<Value, CompileError> {
+        match (value.convert(type_), value) {
+            (Err(Fault::OutOfRange), Value::F64(value)) if !self.checks => Ok(Value::I64(value as i64)),
+            (result, _) => result.map_err(|fault| self.trap(fault, span)),
+        }
+    }
+
+    /// A failed runtime check, with the same message as the JIT's.
+    fn trap(&self, fault: impl Display, span: Span) -> CompileError {
+        let message = match &self.source {
+            Some(source) => {
+                let (line, column) = source.line_col(span.start);
+                format!("{} at {}:{}:{}", fault, source.name, line, column)
+            },
+            None => fault.to_string(),
+        };
+        CompileError::new(ErrorKind::Runtime, message).with_span(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn run(data: &str) -> Result<f64, CompileError> {
+        let program = Parser::from(data).collect::<Result<Vec<_>, _>>().expect("test programs are valid");
+        Interpreter::new().run(&program)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(run("1 + 2 * 3 - 8 / 4").unwrap(), 5.);
+        assert_eq!(run("1 2").unwrap(), 2.);
+        assert_eq!(run("").unwrap(), 0.);
+    }
+
+    #[test]
+    fn test_blocks_and_variables() {
+        assert_eq!(run("{ let x := 2; let y: f64 = { let x := 3; x * x }; x + y }").unwrap(), 11.);
+        assert_eq!(run("{ return 1; 1 as i64 / (0 as i64) }").unwrap(), 1.);
+        assert!(run("{ { let x := 1; x } + x }").is_err());
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(run("{ let x: i64 = 0 as i64 - 7 as i64; x / (2 as i64) }").unwrap(), -3.);
+        assert_eq!(run("(4611686018427387904 as i64 * (2 as i64)) as f64").unwrap(), i64::MIN as f64);
+    }
+
+    #[test]
+    fn test_runtime_checks() {
+        let source = SourceFile::new("test.x", "{ let zero := 0 as i64;\n1 as i64 / zero }");
+        let program = Parser::from(source.text.as_str()).collect::<Result<Vec<_>, _>>().unwrap();
+        let error = Interpreter::new().with_source(&source).run(&program).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Runtime);
+        assert_eq!(error.message, "division by zero at test.x:2:10");
+
+        assert_eq!(run("(0 / 0) as i64").unwrap_err().message, "out-of-range conversion to i64");
+        let program = Parser::from("(1 / 0) as i64").collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(Interpreter::new().with_checks(false).run(&program).unwrap(), i64::MAX as f64);
+    }
+
+    #[test]
+    fn test_asserts() {
+        assert_eq!(run("{ assert(1 as i64 < 2 as i64); assert(0 / 0 != 0 / 0); 1 }").unwrap(), 1.);
+        assert_eq!(run("{ assert(1 >= 2); 1 }").unwrap_err().message, "assertion failed");
+        let source = SourceFile::new("test.x", "{ assert(1 + 1 == 3);\n1 }");
+        let program = Parser::from(source.text.as_str()).collect::<Result<Vec<_>, _>>().unwrap();
+        let error = Interpreter::new().with_source(&source).with_checks(false).run(&program).unwrap_err();
+        assert_eq!(error.message, "assertion failed: 1 + 1 == 3 at test.x:1:10");
+    }
+}
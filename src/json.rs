@@ -0,0 +1,209 @@
+//! Diagnostics as JSON, for editors and CI, with `--error-format=json`.
+//!
+//! Each diagnostic is printed to stderr as one JSON object on its own line,
+//! and nothing else is printed there. Objects have these fields:
+//!
+//! - `severity`: `"error"`, `"warning"`, `"note"` or `"help"`.
+//! - `code`: the error code, such as `"E0001"`, or `null`.
+//! - `message`: the main message, without location.
+//! - `file`: the name of the source file, `"<stdin>"` or `"<eval>"`.
+//! - `spans`: the locations the diagnostic is about, see below.
+//! - `children`: notes and help attached to the diagnostic, as objects with
+//!   `severity` (`"note"` or `"help"`), `message` and `spans`. Suggested
+//!   fixes are `help` children whose spans carry a replacement.
+//! - `rendered`: the diagnostic as it is shown without `--error-format`,
+//!   without colours.
+//!
+//! Spans have these fields:
+//!
+//! - `byte_start`, `byte_end`: offsets of the span in the source, in bytes,
+//!   `byte_end` excluded.
+//! - `line_start`, `column_start`, `line_end`, `column_end`: the same as
+//!   one-based lines and columns, with columns counted in characters.
+//! - `is_primary`: whether this is the main location of the diagnostic.
+//! - `label`: the message shown at the span, or `null`.
+//! - `suggested_replacement`: the text to replace the span with to apply a
+//!   suggestion, or `null`.
+//! - `suggestion_applicability`: for suggestions, `"MachineApplicable"` if
+//!   they can be applied without checking them, as `lang fix` does, or
+//!   `"MaybeIncorrect"`; otherwise `null`.
+//!
+//! The schema is stable: fields may be added but existing ones are neither
+//! removed nor changed in meaning.
+
+use serde::Serialize;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::Applicability;
+use crate::source::SourceFile;
+use crate::span::Span;
+
+#[derive(Debug, Serialize)]
+struct JsonDiagnostic<'d> {
+    severity: Severity,
+    code: Option<&'d str>,
+    message: &'d str,
+    file: &'d str,
+    spans: Vec<JsonSpan<'d>>,
+    children: Vec<JsonChild<'d>>,
+    rendered: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonChild<'d> {
+    severity: Severity,
+    message: &'d str,
+    spans: Vec<JsonSpan<'d>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonSpan<'d> {
+    byte_start: usize,
+    byte_end: usize,
+    line_start: usize,
+    column_start: usize,
+    line_end: usize,
+    column_end: usize,
+    is_primary: bool,
+    label: Option<&'d str>,
+    suggested_replacement: Option<&'d str>,
+    suggestion_applicability: Option<Applicability>,
+}
+
+impl<'d> JsonSpan<'d> {
+    fn new(source: &SourceFile, span: Span, is_primary: bool) -> Self {
+        let (line_start, column_start) = source.line_col(span.start);
+        let (line_end, column_end) = source.line_col(span.end);
+        JsonSpan {
+            byte_start: span.start,
+            byte_end: span.end,
+            line_start,
+            column_start,
+            line_end,
+            column_end,
+            is_primary,
+            label: None,
+            suggested_replacement: None,
+            suggestion_applicability: None,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic as a JSON object on one line, see the module
+    /// documentation for its schema.
+    pub fn to_json(&self, source: &SourceFile) -> String {
+        let spans = self
+            .span
+            .map(|span| JsonSpan::new(source, span, true))
+            .into_iter()
+            .chain(self.labels.iter().map(|label| JsonSpan {
+                label: Some(&label.message),
+                ..JsonSpan::new(source, label.span, false)
+            }))
+            .collect();
+        let notes = self.notes.iter().map(|note| (Severity::Note, note, Vec::new()));
+        let help = self.help.iter().map(|help| (Severity::Help, help, Vec::new()));
+        let suggestions = self.suggestions.iter().map(|suggestion| {
+            let span = JsonSpan {
+                suggested_replacement: Some(&suggestion.replacement),
+                suggestion_applicability: Some(suggestion.applicability),
+                ..JsonSpan::new(source, suggestion.span, true)
+            };
+            (Severity::Help, &suggestion.message, vec![span])
+        });
+        let children = notes
+            .chain(help)
+            .chain(suggestions)
+            .map(|(severity, message, spans)| JsonChild { severity, message, spans })
+            .collect();
+        let json = JsonDiagnostic {
+            severity: self.severity,
+            code: self.code.as_deref(),
+            message: &self.message,
+            file: &source.name,
+            spans,
+            children,
+            rendered: anstream::adapter::strip_str(&self.render(source)).to_string(),
+        };
+        serde_json::to_string(&json).expect("diagnostics can always be serialized")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::error::CompileError;
+
+    fn to_json(diagnostic: impl Into<Diagnostic>, text: &str) -> Value {
+        let source = SourceFile::new("test.x", text);
+        serde_json::from_str(&diagnostic.into().to_json(&source)).unwrap()
+    }
+
+    #[test]
+    fn test_schema() {
+        let error = CompileError::parse("Expected operator ')' but found end of input", Span::new(12, 12))
+            .with_label(Span::new(6, 7), "unclosed parenthesis")
+            .with_note("a note");
+        let mut diagnostic = Diagnostic::from(error);
+        diagnostic.code = Some("E0001".to_string());
+        let json = to_json(diagnostic, "1 +\n 2 * (3 + 4");
+        assert_eq!(
+            json,
+            json!({
+                "severity": "error",
+                "code": "E0001",
+                "message": "Expected operator ')' but found end of input",
+                "file": "test.x",
+                "spans": [
+                    {
+                        "byte_start": 12, "byte_end": 12,
+                        "line_start": 2, "column_start": 9, "line_end": 2, "column_end": 9,
+                        "is_primary": true, "label": null,
+                        "suggested_replacement": null, "suggestion_applicability": null,
+                    },
+                    {
+                        "byte_start": 6, "byte_end": 7,
+                        "line_start": 2, "column_start": 3, "line_end": 2, "column_end": 4,
+                        "is_primary": false, "label": "unclosed parenthesis",
+                        "suggested_replacement": null, "suggestion_applicability": null,
+                    },
+                ],
+                "children": [{ "severity": "note", "message": "a note", "spans": [] }],
+                "rendered": json["rendered"],
+            }),
+        );
+        assert!(json["rendered"].as_str().unwrap().starts_with("error[E0001]: Expected operator ')'"));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let diagnostic = Diagnostic::new(Severity::Warning, "unused variable: 'é'")
+            .with_suggestion(Span::new(6, 8), "_é", "prefix it with an underscore", Applicability::MachineApplicable);
+        let json = to_json(diagnostic, "{ let é := 1; 2 }");
+        assert_eq!(json["code"], Value::Null);
+        assert_eq!(json["spans"], json!([]));
+        assert_eq!(
+            json["children"],
+            json!([{
+                "severity": "help",
+                "message": "prefix it with an underscore",
+                "spans": [{
+                    "byte_start": 6, "byte_end": 8,
+                    "line_start": 1, "column_start": 7, "line_end": 1, "column_end": 8,
+                    "is_primary": true, "label": null,
+                    "suggested_replacement": "_é", "suggestion_applicability": "MachineApplicable",
+                }],
+            }]),
+        );
+    }
+
+    #[test]
+    fn test_one_line() {
+        let error = CompileError::codegen("multi\nline");
+        let source = SourceFile::new("test.x", "");
+        assert_eq!(Diagnostic::from(error).to_json(&source).lines().count(), 1);
+    }
+}
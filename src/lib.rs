@@ -0,0 +1,30 @@
+#![feature(box_patterns)]
+
+pub mod tokenizer;
+pub mod parser;
+pub mod expression;
+pub mod codegen;
+pub mod interp;
+pub mod fold;
+pub mod testing;
+pub mod runtime;
+pub mod error;
+pub mod code;
+pub mod block;
+pub mod span;
+pub mod interner;
+pub mod source;
+pub mod diagnostic;
+pub mod attribute;
+pub mod lint;
+pub mod json;
+pub mod suggest;
+pub mod resolve;
+pub mod typeck;
+pub mod frontend;
+pub mod fix;
+pub mod repl;
+pub mod value;
+pub mod format;
+pub mod dump;
+pub mod lsp;
@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::attribute::Attribute;
+use crate::block::{Block, Line};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::Applicability;
+use crate::expression::Expression;
+use crate::fold;
+use crate::parser::Ast;
+use crate::span::Span;
+use crate::value::Fault;
+
+/// A check for code which compiles but is probably a mistake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// `let` bindings which are never read.
+    UnusedVariables,
+    /// Lines after a `return`.
+    UnreachableCode,
+    /// `let` bindings hiding a variable of an outer block.
+    Shadowing,
+    /// Division by the literal `0`, possibly converted with `as`.
+    DivisionByZero,
+    /// Operations on constants which fail the runtime checks whenever they
+    /// run, e.g. an `i64` division by a variable bound to `0`. Denied by
+    /// default.
+    UnconditionalFault,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] =
+        [Lint::UnusedVariables, Lint::UnreachableCode, Lint::Shadowing, Lint::DivisionByZero, Lint::UnconditionalFault];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused_variables",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::Shadowing => "shadowing",
+            Lint::DivisionByZero => "division_by_zero",
+            Lint::UnconditionalFault => "unconditional_fault",
+        }
+    }
+
+    /// The level of the lint unless the command line or attributes set it.
+    pub fn default_level(self) -> Level {
+        match self {
+            Lint::UnconditionalFault => Level::Deny,
+            _ => Level::Warn,
+        }
+    }
+
+    pub fn lookup(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        // Accept the spelling of the command line as well, e.g. `unused-variables`.
+        Lint::lookup(&name.replace('-', "_")).ok_or_else(|| {
+            let names = Lint::ALL.map(Lint::name);
+            format!("unknown lint '{}', expected one of: {}", name, names.join(", "))
+        })
+    }
+}
+
+/// What to do when a lint fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        }
+    }
+
+    /// The level set by an attribute such as `#[allow(...)]`.
+    pub fn from_attribute(name: &str) -> Option<Level> {
+        [Level::Allow, Level::Warn, Level::Deny].into_iter().find(|level| level.as_str() == name)
+    }
+
+    fn flag(self) -> &'static str {
+        match self {
+            Level::Allow => "-A",
+            Level::Warn => "-W",
+            Level::Deny => "-D",
+        }
+    }
+}
+
+/// Where the level of a lint was set, to tell the user how to change it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    Default,
+    CommandLine,
+    Attribute(Span),
+}
+
+/// The level of every lint: its default one, then as set on the command
+/// line, then by attributes of the code being checked.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Lint, (Level, Origin)>,
+}
+
+impl LintLevels {
+    /// Sets a level from the command line.
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, (level, Origin::CommandLine));
+    }
+
+    pub fn get(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).map_or(lint.default_level(), |(level, _)| *level)
+    }
+
+    fn origin(&self, lint: Lint) -> Origin {
+        self.levels.get(&lint).map_or(Origin::Default, |(_, origin)| *origin)
+    }
+}
+
+/// Checks a parsed program, with `attributes` being the `#![...]` ones of
+/// its file, and returns the warnings and, for denied lints, errors found.
+pub fn check(program: &[Ast], attributes: &[Attribute], levels: &LintLevels) -> Vec<Diagnostic> {
+    let faults = fold::faults(program).into_iter().collect();
+    let mut linter = Linter { levels: vec![levels.clone()], scopes: vec![Vec::new()], faults, diagnostics: Vec::new() };
+    linter.push_levels(attributes);
+    for ast in program {
+        match ast {
+            Ast::Expression(expression) => linter.expression(expression),
+            Ast::Test(test) => linter.block(&test.body),
+        }
+    }
+    linter.pop_scope();
+    linter.diagnostics
+}
+
+struct Binding {
+    name: String,
+    span: Span,
+    used: bool,
+    /// The level of `unused_variables` where the binding is declared.
+    unused: (Level, Origin),
+}
+
+struct Linter {
+    /// Levels in effect, innermost last.
+    levels: Vec<LintLevels>,
+    /// Bindings of each block being checked, innermost last.
+    scopes: Vec<Vec<Binding>>,
+    /// Operations which always fail, by span, see `fold::faults`.
+    faults: HashMap<Span, Fault>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn levels(&self) -> &LintLevels {
+        self.levels.last().expect("the levels of the command line are never popped")
+    }
+
+    fn push_levels(&mut self, attributes: &[Attribute]) {
+        let mut levels = self.levels().clone();
+        for attribute in attributes {
+            let Some(level) = Level::from_attribute(&attribute.name) else {
+                continue;
+            };
+            for (name, span) in &attribute.args {
+                match Lint::lookup(name) {
+                    Some(lint) => {
+                        levels.levels.insert(lint, (level, Origin::Attribute(*span)));
+                    },
+                    None => self.diagnostics.push(
+                        Diagnostic::new(Severity::Warning, format!("unknown lint: '{}'", name))
+                            .with_span(*span)
+                            .with_note(format!("known lints are: {}", Lint::ALL.map(Lint::name).join(", "))),
+                    ),
+                }
+            }
+        }
+        self.levels.push(levels);
+    }
+
+    fn emit(&mut self, lint: Lint, diagnostic: Diagnostic) {
+        let level = (self.levels().get(lint), self.levels().origin(lint));
+        self.emit_at(lint, level, diagnostic);
+    }
+
+    /// Emits a diagnostic of `lint` as warning or error depending on `level`,
+    /// explaining where the level comes from.
+    fn emit_at(&mut self, lint: Lint, (level, origin): (Level, Origin), mut diagnostic: Diagnostic) {
+        diagnostic.severity = match level {
+            Level::Allow => return,
+            Level::Warn => Severity::Warning,
+            Level::Deny => Severity::Error,
+        };
+        diagnostic = match origin {
+            Origin::Default => diagnostic.with_note(format!("'#[{}({})]' on by default", level.as_str(), lint)),
+            Origin::CommandLine => {
+                diagnostic.with_note(format!("requested on the command line with '{} {}'", level.flag(), lint))
+            },
+            Origin::Attribute(span) => diagnostic.with_label(span, "the lint level is defined here"),
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn pop_scope(&mut self) {
+        for binding in self.scopes.pop().unwrap_or_default() {
+            if !binding.used && !binding.name.starts_with('_') {
+                let diagnostic = Diagnostic::new(Severity::Warning, format!("unused variable: '{}'", binding.name))
+                    .with_span(binding.span)
+                    .with_suggestion(
+                        binding.span,
+                        format!("_{}", binding.name),
+                        "if this is intentional, prefix it with an underscore",
+                        Applicability::MachineApplicable,
+                    );
+                self.emit_at(Lint::UnusedVariables, binding.unused, diagnostic);
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.push_levels(&block.attributes);
+        self.scopes.push(Vec::new());
+        let returns = block.lines.iter().position(|line| matches!(line, Line::ReturnStatement(_)));
+        if let Some(i) = returns.filter(|i| i + 1 < block.lines.len()) {
+            let span = block.info[i + 1].span.to(block.info[block.info.len() - 1].span);
+            let diagnostic = Diagnostic::new(Severity::Warning, "unreachable code")
+                .with_span(span)
+                .with_label(block.info[i].span, "any code following this return is unreachable");
+            self.emit(Lint::UnreachableCode, diagnostic);
+        }
+        for (line, info) in block.lines.iter().zip(&block.info) {
+            self.push_levels(&info.attributes);
+            self.line(line);
+            self.levels.pop();
+        }
+        self.pop_scope();
+        self.levels.pop();
+    }
+
+    fn line(&mut self, line: &Line) {
+        match line {
+            Line::Expression(expression) | Line::ReturnStatement(expression) => self.expression(expression),
+            Line::LetStatement { name, span, value, .. } => {
+                self.expression(value);
+                let outer = self.scopes[..self.scopes.len() - 1]
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.iter().rev().find(|binding| binding.name == *name));
+                if let Some(outer) = outer {
+                    let diagnostic = Diagnostic::new(Severity::Warning, format!("'{}' shadows a variable of an outer block", name))
+                        .with_span(*span)
+                        .with_label(outer.span, "the shadowed variable is declared here")
+                        .with_help("use another name to keep both variables apart");
+                    self.emit(Lint::Shadowing, diagnostic);
+                }
+                let unused = (self.levels().get(Lint::UnusedVariables), self.levels().origin(Lint::UnusedVariables));
+                let binding = Binding { name: name.clone(), span: *span, used: false, unused };
+                self.scopes.last_mut().expect("there is always a scope").push(binding);
+            },
+            Line::Assert { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            },
+            Line::Error(_) => (),
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Literal(..) => (),
+            Expression::Variable(name, _) => {
+                let binding = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find_map(|scope| scope.iter_mut().rev().find(|binding| binding.name == *name));
+                if let Some(binding) = binding {
+                    binding.used = true;
+                }
+            },
+            Expression::Block(block) => self.block(block),
+            Expression::Cast(value, _, span) => {
+                self.expression(value);
+                self.fault(*span);
+            },
+            Expression::Add(a, b, span) | Expression::Sub(a, b, span) | Expression::Mul(a, b, span) => {
+                self.expression(a);
+                self.expression(b);
+                self.fault(*span);
+            },
+            Expression::Div(a, b, span) => {
+                self.expression(a);
+                self.expression(b);
+                if self.fault(*span) {
+                    return;
+                }
+                let divisor = match &**b {
+                    Expression::Cast(value, _, _) => value,
+                    divisor => divisor,
+                };
+                if matches!(divisor, Expression::Literal(divisor, _) if *divisor == 0.) {
+                    let diagnostic = Diagnostic::new(Severity::Warning, "division by zero")
+                        .with_span(*span)
+                        .with_note("the divisor is the literal '0'");
+                    self.emit(Lint::DivisionByZero, diagnostic);
+                }
+            },
+        }
+    }
+
+    /// Reports the operation at `span` if it always fails, returning
+    /// whether it does.
+    fn fault(&mut self, span: Span) -> bool {
+        let Some(fault) = self.faults.get(&span) else {
+            return false;
+        };
+        let diagnostic = Diagnostic::new(Severity::Error, format!("this operation will fail at runtime: {}", fault))
+            .with_span(span)
+            .with_note("its operands are constants");
+        self.emit(Lint::UnconditionalFault, diagnostic);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn lint(data: &str, levels: &LintLevels) -> Vec<Diagnostic> {
+        let mut parser = Parser::from(data);
+        let program = parser.by_ref().collect::<Result<Vec<_>, _>>().expect("test programs are valid");
+        check(&program, &parser.attributes, levels)
+    }
+
+    fn messages(data: &str) -> Vec<String> {
+        lint(data, &LintLevels::default()).into_iter().map(|d| d.message).collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        assert_eq!(messages("{ let x := 1; let _y := 2; let z := 3; z }"), ["unused variable: 'x'"]);
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let diagnostics = lint("{ return 1; 2; 3 }", &LintLevels::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unreachable code");
+        assert_eq!(diagnostics[0].span, Some(Span::new(12, 16)));
+        assert_eq!(diagnostics[0].labels[0].span, Span::new(2, 10));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let diagnostics = lint("{ let x := 1; { let x := 2; x } + x }", &LintLevels::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "'x' shadows a variable of an outer block");
+        assert_eq!(diagnostics[0].labels[0].span, Span::new(6, 7));
+        // Declaring a name again in the same block is not shadowing.
+        assert!(messages("{ let x := 1; let x := x; x }").is_empty());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(messages("1 / 0 + 1 / 2 + 1 / (0)"), ["division by zero", "division by zero"]);
+        // Division by an `i64` zero is a fault, see below.
+        assert_eq!(messages("1 as i64 / 0 as i64"), ["this operation will fail at runtime: division by zero"]);
+    }
+
+    #[test]
+    fn test_unconditional_fault() {
+        let data = "{\n  let zero := 0 as i64;\n  let big := 1 / (0 * 1);\n  #[allow(unconditional_fault)]\n  let _x := 1 as i64 / zero;\n  big as i64\n}";
+        let diagnostics = lint(data, &LintLevels::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "this operation will fail at runtime: out-of-range conversion to i64");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].span, Some(Span::new(119, 125)));
+        assert!(diagnostics[0].notes.contains(&"'#[deny(unconditional_fault)]' on by default".to_string()));
+    }
+
+    #[test]
+    fn test_levels_from_command_line() {
+        let mut levels = LintLevels::default();
+        levels.set(Lint::DivisionByZero, Level::Deny);
+        levels.set(Lint::UnusedVariables, Level::Allow);
+        let diagnostics = lint("{ let x := 1; 1 / 0 }", &levels);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].notes.iter().any(|note| note.contains("-D division_by_zero")));
+    }
+
+    #[test]
+    fn test_levels_from_attributes() {
+        let data = "#![deny(unused_variables)]\n{\n  #![allow(division_by_zero)]\n  let x := 1 / 0;\n  #[allow(unused_variables)]\n  let y := 2;\n  3\n}";
+        let diagnostics = lint(data, &LintLevels::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unused variable: 'x'");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].labels[0].message, "the lint level is defined here");
+    }
+
+    #[test]
+    fn test_unknown_lint() {
+        assert_eq!(messages("#![allow(unused)]\n1"), ["unknown lint: 'unused'"]);
+        assert!("unused-variables".parse::<Lint>().is_ok());
+        assert!("unused".parse::<Lint>().is_err());
+    }
+}
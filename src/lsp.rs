@@ -0,0 +1,430 @@
+//! A language server for editors, speaking the Language Server Protocol
+//! over stdio with `lang lsp`.
+//!
+//! The server keeps the text of the open documents as the editor sends it,
+//! saved or not, and checks it on every change. It provides:
+//!
+//! - diagnostics: the errors and warnings of the front-end;
+//! - hover: the type of a `let` binding, on its name or on a variable
+//!   referring to it;
+//! - go to definition: from a variable to its `let` binding;
+//! - document symbols: the `let` bindings, nested as their blocks are;
+//! - semantic tokens: keywords, numbers, variables, types, operators and
+//!   comments, for the whole document.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _, SemanticTokensFullRequest};
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability, Location,
+    MarkupContent, MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range, SemanticToken,
+    SemanticTokenType, SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
+    SemanticTokensParams, SemanticTokensResult, ServerCapabilities, SymbolKind, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Uri,
+};
+
+use crate::block::{Block, Line};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::expression::Expression;
+use crate::frontend::{self, Checked};
+use crate::lint::LintLevels;
+use crate::parser::Ast;
+use crate::resolve;
+use crate::source::SourceFile;
+use crate::span::Span;
+use crate::tokenizer::{Keyword, Token, Tokenizer};
+use crate::typeck::{self, Type};
+
+/// Semantic token types, in the order of the legend sent to the client.
+const TOKEN_TYPES: [SemanticTokenType; 7] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::STRING,
+];
+
+/// Runs the server over stdio until the client asks it to exit.
+pub fn serve() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, io_threads) = Connection::stdio();
+    run(&connection)?;
+    // The writer thread only stops once the connection is gone.
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Initializes a connection and answers its messages until the client
+/// shuts the server down.
+pub fn run(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server { documents: HashMap::new() };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection.sender.send(server.request(request).into())?;
+            },
+            Message::Notification(notification) => {
+                if let Some(diagnostics) = server.notification(notification) {
+                    connection.sender.send(diagnostics.into())?;
+                }
+            },
+            Message::Response(_) => (),
+        }
+    }
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    let semantic_tokens = SemanticTokensOptions {
+        legend: SemanticTokensLegend { token_types: TOKEN_TYPES.to_vec(), token_modifiers: Vec::new() },
+        full: Some(SemanticTokensFullOptions::Bool(true)),
+        ..Default::default()
+    };
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(semantic_tokens.into()),
+        ..Default::default()
+    }
+}
+
+struct Server {
+    documents: HashMap<Uri, Document>,
+}
+
+/// An open document as last sent by the client, checked.
+struct Document {
+    source: SourceFile,
+    checked: Checked,
+    /// Variables and the name in the `let` binding they refer to.
+    definitions: Vec<(Span, Span)>,
+    /// Names in `let` bindings and their types.
+    bindings: Vec<(Span, Type)>,
+}
+
+impl Document {
+    fn new(uri: &Uri, text: String) -> Self {
+        let checked = frontend::check(&text, &LintLevels::default());
+        let definitions = resolve::definitions(&checked.program);
+        let bindings = typeck::bindings(&checked.program);
+        Document { source: SourceFile::new(uri.as_str(), text), checked, definitions, bindings }
+    }
+
+    /// The `let` binding named at `offset`, by the span of its name, and the
+    /// span of that name or of the variable referring to the binding.
+    fn binding_at(&self, offset: usize) -> Option<(Span, Span)> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        let variable = self.definitions.iter().find(|(variable, _)| contains(variable));
+        let binding = self.bindings.iter().find(|(name, _)| contains(name)).map(|(name, _)| (*name, *name));
+        variable.map(|(variable, definition)| (*definition, *variable)).or(binding)
+    }
+
+    fn type_of(&self, binding: Span) -> Option<Type> {
+        self.bindings.iter().find(|(name, _)| *name == binding).map(|(_, type_)| *type_)
+    }
+
+    fn diagnostics(&self, uri: &Uri) -> Vec<lsp_types::Diagnostic> {
+        self.checked.diagnostics().map(|diagnostic| self.diagnostic(uri, &diagnostic)).collect()
+    }
+
+    /// A diagnostic for the client, with its notes, help and suggestions
+    /// in the message as they are rendered for the command line.
+    fn diagnostic(&self, uri: &Uri, diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+        let mut message = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            message.push_str(&format!("\nnote: {}", note));
+        }
+        for help in &diagnostic.help {
+            message.push_str(&format!("\nhelp: {}", help));
+        }
+        for suggestion in &diagnostic.suggestions {
+            message.push_str(&format!("\nhelp: {}: '{}'", suggestion.message, suggestion.replacement));
+        }
+        let related = diagnostic.labels.iter().map(|label| DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), range(&self.source, label.span)),
+            message: label.message.clone(),
+        });
+        lsp_types::Diagnostic {
+            range: range(&self.source, diagnostic.span.unwrap_or_default()),
+            severity: Some(match diagnostic.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+                Severity::Note => DiagnosticSeverity::INFORMATION,
+                Severity::Help => DiagnosticSeverity::HINT,
+            }),
+            code: diagnostic.code.clone().map(NumberOrString::String),
+            source: Some("lang".to_string()),
+            message,
+            related_information: Some(related.collect()).filter(|related: &Vec<_>| !related.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    /// The tests of a program, with the bindings of their body as children,
+    /// and the `let` bindings of its expressions.
+    fn program_symbols(&self, symbols: &mut Vec<DocumentSymbol>) {
+        for ast in &self.checked.program {
+            match ast {
+                Ast::Expression(expression) => self.symbols(expression, symbols),
+                Ast::Test(test) => {
+                    let mut children = Vec::new();
+                    self.block_symbols(&test.body, &mut children);
+                    #[allow(deprecated)]
+                    symbols.push(DocumentSymbol {
+                        name: test.name.clone(),
+                        detail: Some("test".to_string()),
+                        kind: SymbolKind::FUNCTION,
+                        tags: None,
+                        deprecated: None,
+                        range: range(&self.source, test.span),
+                        selection_range: range(&self.source, test.name_span),
+                        children: Some(children).filter(|children| !children.is_empty()),
+                    });
+                },
+            }
+        }
+    }
+
+    /// The `let` bindings of an expression, each with those of its value
+    /// as children.
+    fn symbols(&self, expression: &Expression, symbols: &mut Vec<DocumentSymbol>) {
+        match expression {
+            Expression::Literal(..) | Expression::Variable(..) => (),
+            Expression::Block(block) => self.block_symbols(block, symbols),
+            Expression::Add(a, b, _) | Expression::Sub(a, b, _) | Expression::Mul(a, b, _) | Expression::Div(a, b, _) => {
+                self.symbols(a, symbols);
+                self.symbols(b, symbols);
+            },
+            Expression::Cast(value, _, _) => self.symbols(value, symbols),
+        }
+    }
+
+    fn block_symbols(&self, block: &Block, symbols: &mut Vec<DocumentSymbol>) {
+        for (line, info) in block.lines.iter().zip(&block.info) {
+            match line {
+                Line::Expression(expression) | Line::ReturnStatement(expression) => self.symbols(expression, symbols),
+                Line::LetStatement { name, span, value, .. } => {
+                    let mut children = Vec::new();
+                    self.symbols(value, &mut children);
+                    #[allow(deprecated)]
+                    symbols.push(DocumentSymbol {
+                        name: name.clone(),
+                        detail: self.type_of(*span).map(|type_| type_.to_string()),
+                        kind: SymbolKind::VARIABLE,
+                        tags: None,
+                        deprecated: None,
+                        range: range(&self.source, info.span),
+                        selection_range: range(&self.source, *span),
+                        children: Some(children).filter(|children| !children.is_empty()),
+                    });
+                },
+                Line::Assert { left, right, .. } => {
+                    self.symbols(left, symbols);
+                    self.symbols(right, symbols);
+                },
+                Line::Error(_) => (),
+            }
+        }
+    }
+}
+
+impl Server {
+    /// Updates the open documents, returning their new diagnostics.
+    fn notification(&mut self, notification: Notification) -> Option<Notification> {
+        let (uri, text) = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD).ok()?;
+                (params.text_document.uri, Some(params.text_document.text))
+            },
+            DidChangeTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD).ok()?;
+                // The server asks for the whole text on each change.
+                (params.text_document.uri, Some(params.content_changes.into_iter().last()?.text))
+            },
+            DidCloseTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD).ok()?;
+                (params.text_document.uri, None)
+            },
+            _ => return None,
+        };
+        let diagnostics = match text {
+            Some(text) => {
+                let document = Document::new(&uri, text);
+                let diagnostics = document.diagnostics(&uri);
+                self.documents.insert(uri.clone(), document);
+                diagnostics
+            },
+            None => {
+                self.documents.remove(&uri);
+                Vec::new()
+            },
+        };
+        let params = PublishDiagnosticsParams { uri, diagnostics, version: None };
+        Some(Notification::new(PublishDiagnostics::METHOD.to_string(), params))
+    }
+
+    fn request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            HoverRequest::METHOD => self.handle::<HoverRequest>(request, Server::hover),
+            GotoDefinition::METHOD => self.handle::<GotoDefinition>(request, Server::definition),
+            DocumentSymbolRequest::METHOD => self.handle::<DocumentSymbolRequest>(request, Server::symbols),
+            SemanticTokensFullRequest::METHOD => self.handle::<SemanticTokensFullRequest>(request, Server::semantic_tokens),
+            method => Response::new_err(request.id, ErrorCode::MethodNotFound as i32, format!("unknown method '{}'", method)),
+        }
+    }
+
+    fn handle<R: lsp_types::request::Request>(&self, request: Request, f: fn(&Self, R::Params) -> R::Result) -> Response {
+        match serde_json::from_value(request.params) {
+            Ok(params) => Response::new_ok(request.id, f(self, params)),
+            Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let (binding, at) = document.binding_at(offset(&document.source, position.position)?)?;
+        let type_ = document.type_of(binding)?;
+        let value = format!("```lang\nlet {}: {}\n```", document.source.slice(binding), type_);
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+            range: Some(range(&document.source, at)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let (binding, _) = document.binding_at(offset(&document.source, position.position)?)?;
+        Some(Location::new(position.text_document.uri, range(&document.source, binding)).into())
+    }
+
+    fn symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let mut symbols = Vec::new();
+        document.program_symbols(&mut symbols);
+        Some(symbols.into())
+    }
+
+    fn semantic_tokens(&self, params: SemanticTokensParams) -> Option<SemanticTokensResult> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        Some(SemanticTokens { result_id: None, data: semantic_tokens(&document.source) }.into())
+    }
+}
+
+/// The tokens of a source with their index in `TOKEN_TYPES`, relative to
+/// the previous one as the protocol wants. Symbols after `:` or `as` are
+/// types; invalid tokens are left out.
+fn semantic_tokens(source: &SourceFile) -> Vec<SemanticToken> {
+    let mut tokenizer = Tokenizer::from(source.text.as_str());
+    let mut tokens = Vec::new();
+    let mut previous = None;
+    while let Some((token, span)) = tokenizer.next_raw() {
+        let type_ = match token {
+            Token::Keyword(_) => 0,
+            Token::Number => 1,
+            Token::Symbol(_) if matches!(previous, Some(Token::Operator(':') | Token::Keyword(Keyword::As))) => 3,
+            Token::Symbol(_) => 2,
+            Token::Operator(_) => 4,
+            Token::String => 6,
+            Token::Error(_) => continue,
+        };
+        previous = Some(token);
+        tokens.push((span, type_));
+    }
+    tokens.extend(tokenizer.comments().iter().map(|span| (*span, 5)));
+    tokens.sort_by_key(|(span, _)| span.start);
+
+    let mut last = Position::new(0, 0);
+    tokens
+        .into_iter()
+        .map(|(span, type_)| {
+            let start = position(source, span.start);
+            let delta_line = start.line - last.line;
+            let delta_start = if delta_line == 0 { start.character - last.character } else { start.character };
+            last = start;
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: source.slice(span).trim_end().encode_utf16().count() as u32,
+                token_type: type_,
+                token_modifiers_bitset: 0,
+            }
+        })
+        .collect()
+}
+
+/// The position of a byte offset. Characters are counted in UTF-16 code
+/// units, as the protocol does by default.
+fn position(source: &SourceFile, offset: usize) -> Position {
+    let offset = offset.min(source.text.len());
+    let line = source.line_index(offset);
+    let start = source.line_range(line).start;
+    Position::new(line as u32, source.text[start..offset.max(start)].encode_utf16().count() as u32)
+}
+
+fn range(source: &SourceFile, span: Span) -> Range {
+    Range::new(position(source, span.start), position(source, span.end))
+}
+
+/// The byte offset of a position, clamped to the end of its line.
+fn offset(source: &SourceFile, position: Position) -> Option<usize> {
+    let line = position.line as usize;
+    if line >= source.line_count() {
+        return None;
+    }
+    let range = source.line_range(line);
+    let mut units = 0;
+    for (i, c) in source.text[range.clone()].char_indices() {
+        if units >= position.character as usize {
+            return Some(range.start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions() {
+        let source = SourceFile::new("test.x", "{\r\n  let 𝑥 := 1;\n  𝑥 }");
+        assert_eq!(position(&source, 9), Position::new(1, 6));
+        assert_eq!(position(&source, 13), Position::new(1, 8));
+        assert_eq!(offset(&source, Position::new(1, 8)), Some(13));
+        assert_eq!(offset(&source, Position::new(0, 99)), Some(1));
+        assert_eq!(offset(&source, Position::new(3, 0)), None);
+        for offset_ in [0, 1, 3, 9, 13, 22, source.text.len()] {
+            assert_eq!(offset(&source, position(&source, offset_)), Some(offset_), "{}", offset_);
+        }
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let source = SourceFile::new("test.x", "// hi\nlet x: i64 = 1 as f64;");
+        let tokens = semantic_tokens(&source)
+            .iter()
+            .map(|token| (token.delta_line, token.delta_start, token.length, token.token_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            [(0, 0, 5, 5), (1, 0, 3, 0), (0, 4, 1, 2), (0, 1, 1, 4), (0, 2, 3, 3), (0, 4, 1, 4), (0, 2, 1, 1), (0, 2, 2, 0), (0, 3, 3, 3), (0, 3, 1, 4)],
+        );
+    }
+}
@@ -1,47 +1,362 @@
-#![feature(box_patterns)]
+use std::io::{BufRead, IsTerminal, Write};
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::sync::Mutex;
+use std::{fs, io, path::{Path, PathBuf}, process::ExitCode};
 
-use std::{error::Error, path::PathBuf};
+use inkwell::context::Context;
+use lang::codegen::CodeGen;
+use lang::interp::Interpreter;
+use lang::code::ErrorCode;
+use lang::diagnostic::{Diagnostic, Emitter, ErrorFormat, Severity};
+use lang::error::{CompileError, ErrorKind};
+use lang::lint::{Level, Lint, LintLevels};
+use lang::runtime::TRAP_EXIT_CODE;
+use lang::source::SourceFile;
+use lang::dump::{self, Dump};
+use lang::{fix, fold, format, frontend, lsp, parser, repl, testing};
 
-use inkwell::{context::Context, OptimizationLevel};
-use crate::codegen::CodeGen;
-
-pub mod tokenizer;
-pub mod parser;
-pub mod expression;
-pub mod codegen;
-pub mod error;
-pub mod block;
-
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 /// A simple compiler for a simple language
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true, arg_required_else_help = true)]
 struct Args {
-    /// The path to the file to compile and run
-    #[arg()]
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Explain an error code, such as E0011
+    #[arg(long, value_name = "CODE")]
+    explain: Option<ErrorCode>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile and run a program
+    Run(RunArgs),
+    /// Report the errors and warnings of programs without compiling them
+    Check(CheckArgs),
+    /// Apply the fixes suggested for a program which are certainly right
+    Fix(FixArgs),
+    /// Evaluate entries interactively, keeping the variables they define
+    Repl(ReplArgs),
+    /// Run the tests of a program, its `test "name" { ... }` items
+    Test(TestArgs),
+    /// Format programs in place
+    Fmt(FmtArgs),
+    /// Run a language server for editors, over stdio
+    Lsp,
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    #[command(flatten)]
+    input: Input,
+    #[command(flatten)]
+    lints: LintArgs,
+    /// How to print errors and warnings: `human` or `json`, one object per
+    /// line
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
+    /// Don't check for division by zero, overflowing division and
+    /// out-of-range conversions at runtime, leaving them undefined
+    #[arg(long)]
+    no_runtime_checks: bool,
+    /// Print the program instead of running it, to debug the front-end:
+    /// `tokens`, `ast` for the syntax tree or `sexp` for it as
+    /// S-expressions
+    #[arg(long, value_name = "WHAT")]
+    dump: Option<Dump>,
+    /// Run the program with the interpreter instead of compiling it
+    #[arg(long, conflicts_with = "dump")]
+    interp: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct CheckArgs {
+    /// The files to check, and directories whose `.x` files to check
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    #[command(flatten)]
+    lints: LintArgs,
+    /// How to print errors and warnings: `human` or `json`, one object per
+    /// line
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct FixArgs {
+    /// The file to fix in place
     path: PathBuf,
+    #[command(flatten)]
+    lints: LintArgs,
+    /// How to print errors and warnings: `human` or `json`, one object per
+    /// line
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct TestArgs {
+    /// The file whose tests to run
+    path: PathBuf,
+    /// Only run the tests whose name contains this
+    filter: Option<String>,
+    #[command(flatten)]
+    lints: LintArgs,
+    /// How to print errors and warnings: `human` or `json`, one object per
+    /// line
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
+    /// Don't check for division by zero, overflowing division and
+    /// out-of-range conversions at runtime, leaving them undefined
+    #[arg(long)]
+    no_runtime_checks: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct FmtArgs {
+    /// The files to format
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    /// Don't write the files, only fail if some of them are not formatted
+    #[arg(long)]
+    check: bool,
+    /// How to print errors: `human` or `json`, one object per line
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReplArgs {
+    #[command(flatten)]
+    lints: LintArgs,
+    /// Don't check for division by zero, overflowing division and
+    /// out-of-range conversions at runtime, leaving them undefined
+    #[arg(long)]
+    no_runtime_checks: bool,
+    /// The file keeping the entries across sessions [default:
+    /// ~/.lang_history]
+    #[arg(long, value_name = "FILE")]
+    history: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct Input {
+    /// The path to the file to compile and run, or `-` to read it from stdin
+    #[arg()]
+    path: Option<PathBuf>,
+    /// Run the given code instead of a file
+    #[arg(short = 'e', long = "eval", value_name = "CODE")]
+    eval: Option<String>,
+}
+
+/// Lint levels. When a lint is given to several of these, `-D` wins over
+/// `-W` which wins over `-A`; attributes in the source override them all.
+#[derive(clap::Args, Debug)]
+struct LintArgs {
+    /// Warn about a lint
+    #[arg(short = 'W', long = "warn", value_name = "LINT")]
+    warn: Vec<Lint>,
+    /// Allow a lint
+    #[arg(short = 'A', long = "allow", value_name = "LINT")]
+    allow: Vec<Lint>,
+    /// Deny a lint, making it an error
+    #[arg(short = 'D', long = "deny", value_name = "LINT")]
+    deny: Vec<Lint>,
+}
+
+impl LintArgs {
+    fn levels(&self) -> LintLevels {
+        let mut levels = LintLevels::default();
+        for (lints, level) in [(&self.allow, Level::Allow), (&self.warn, Level::Warn), (&self.deny, Level::Deny)] {
+            for lint in lints {
+                levels.set(*lint, level);
+            }
+        }
+        levels
+    }
+}
+
+impl Input {
+    fn source(&self) -> io::Result<SourceFile> {
+        match (&self.eval, &self.path) {
+            (Some(code), _) => Ok(SourceFile::new("<eval>", code.as_str())),
+            (None, Some(path)) if path != Path::new("-") => SourceFile::from_path(path),
+            // clap requires either a path or code, so this is `-`.
+            (None, _) => SourceFile::from_reader("<stdin>", io::stdin()),
+        }
+    }
 }
 
 
-fn main() -> Result<(), Box<dyn Error>> {
+/// Exit status when the compiler panics, as rustc's.
+const ICE_EXIT_CODE: u8 = 101;
+
+/// Message and location of the last panic, reported once it is caught.
+static PANIC: Mutex<Option<(String, String)>> = Mutex::new(None);
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    
-    let mut parser = parser::Parser::try_from(args.path.as_path()).expect("Failed to create parser");
-    let ast = parser.next().unwrap()?;
+    let command = match (args.command, args.explain) {
+        (Some(command), _) => command,
+        (None, Some(code)) => return explain(code),
+        (None, None) => unreachable!("clap requires a subcommand or --explain"),
+    };
+    let format = match &command {
+        Command::Run(args) => args.error_format,
+        Command::Check(args) => args.error_format,
+        Command::Fix(args) => args.error_format,
+        Command::Test(args) => args.error_format,
+        Command::Repl(_) | Command::Lsp => ErrorFormat::Human,
+        Command::Fmt(args) => args.error_format,
+    };
+    panic::set_hook(Box::new(record_panic));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| match command {
+        Command::Run(args) => run(args),
+        Command::Check(args) => check(args),
+        Command::Fix(args) => fix(args),
+        Command::Test(args) => test(args),
+        Command::Repl(args) => run_repl(args),
+        Command::Fmt(args) => fmt(args),
+        Command::Lsp => run_lsp(),
+    }));
+    result.unwrap_or_else(|_| {
+        let (message, location) = PANIC.lock().map(|mut panic| panic.take()).ok().flatten().unwrap_or_default();
+        let none = SourceFile::new("", "");
+        Emitter::new(&none).with_format(format).emit(&ice(&message, &location));
+        ExitCode::from(ICE_EXIT_CODE)
+    })
+}
 
+fn record_panic(info: &PanicHookInfo) {
+    let message = match (info.payload().downcast_ref::<&str>(), info.payload().downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string(),
+    };
+    let location = info.location().map(|l| l.to_string()).unwrap_or_default();
+    if let Ok(mut panic) = PANIC.lock() {
+        *panic = Some((message, location));
+    }
+}
 
-    let context = Context::create();
-    let module = context.create_module("sum");
-    let execution_engine = module.create_jit_execution_engine(OptimizationLevel::None)?;
-    let mut codegen = CodeGen {
-        context: &context,
-        module,
-        builder: context.create_builder(),
-        execution_engine,
+/// Report of an internal compiler error, i.e. a panic of the compiler.
+fn ice(message: &str, location: &str) -> Diagnostic {
+    Diagnostic::new(Severity::Error, format!("internal compiler error: {}", message))
+        .with_note(format!("the compiler panicked at {}", location))
+        .with_note("this is a bug in the compiler, not in your program")
+        .with_help("please report it, together with the program which triggered it")
+}
+
+/// Prints the explanation of an error code.
+fn explain(code: ErrorCode) -> ExitCode {
+    println!("{}", code.explanation().trim_end());
+    ExitCode::SUCCESS
+}
+
+/// Reports an I/O error about a file, which has no source to show.
+fn io_error(message: String, format: ErrorFormat) -> ExitCode {
+    let none = SourceFile::new("", "");
+    Emitter::new(&none).with_format(format).emit(&CompileError::new(ErrorKind::Io, message).into());
+    ExitCode::FAILURE
+}
+
+fn run(args: RunArgs) -> ExitCode {
+    let source = match args.input.source() {
+        Ok(source) => source,
+        Err(e) => {
+            let name = args.input.path.as_deref().unwrap_or(Path::new("-")).display();
+            return io_error(format!("Couldn't read {}: {}", name, e), args.error_format);
+        },
     };
+    if let Some(what) = args.dump {
+        return dump(&source, what, args.error_format);
+    }
+    let mut emitter = Emitter::new(&source).with_format(args.error_format);
+    if let Err(errors) = compile_and_run(&source, &args, &mut emitter) {
+        for error in errors {
+            emitter.emit(&error.into());
+        }
+    }
+    emitter.summary();
+    match emitter.errors {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+/// Prints the tokens or the syntax tree of a program. The tokens include
+/// the invalid ones; the tree leaves out the items with syntax errors,
+/// which are reported.
+fn dump(source: &SourceFile, what: Dump, format: ErrorFormat) -> ExitCode {
+    if what == Dump::Tokens {
+        print!("{}", dump::tokens(source));
+        return ExitCode::SUCCESS;
+    }
+    let mut parser = parser::Parser::from(source.text.as_str());
+    let mut items = Vec::new();
+    let mut emitter = Emitter::new(source).with_format(format);
+    while let Some(item) = parser.next() {
+        match item {
+            Ok(ast) => items.push((ast, parser.span())),
+            Err(errors) => {
+                for error in errors {
+                    emitter.emit(&error.into());
+                }
+            },
+        }
+    }
+    match what {
+        Dump::Sexp => {
+            for (ast, _) in &items {
+                println!("{}", dump::sexp_ast(ast));
+            }
+        },
+        _ => print!("{}", dump::tree(&parser.attributes, &items)),
+    }
+    emitter.summary();
+    match emitter.errors {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
 
-    let main = codegen.compile_main(&ast)?;
+/// Compiles and runs a program, emitting its warnings; errors are returned
+/// unless they were emitted already.
+fn compile_and_run(source: &SourceFile, args: &RunArgs, emitter: &mut Emitter) -> Result<(), Vec<CompileError>> {
+    // Check the whole program first, to report all its errors at once.
+    let checked = frontend::check(&source.text, &args.lints.levels());
+    if !checked.errors.is_empty() {
+        return Err(checked.errors);
+    }
+    for diagnostic in &checked.lints {
+        emitter.emit(diagnostic);
+    }
+    if emitter.errors > 0 || checked.program.is_empty() {
+        return Ok(());
+    }
+
+    // The interpreter runs the program as written, as a reference for the
+    // folded one the JIT runs.
+    if args.interp {
+        let mut interpreter = Interpreter::new().with_source(source).with_checks(!args.no_runtime_checks);
+        match interpreter.run(&checked.program) {
+            Ok(value) => println!("output: {}", value),
+            // Stop as compiled programs do, see `runtime::trap`.
+            Err(error) if error.kind == ErrorKind::Runtime => {
+                eprintln!("runtime error: {}", error.message);
+                std::process::exit(TRAP_EXIT_CODE);
+            },
+            Err(error) => return Err(vec![error]),
+        }
+        return Ok(());
+    }
+
+    let context = Context::create();
+    let codegen = CodeGen::new(&context).map_err(|e| vec![e])?;
+    let mut codegen = codegen.with_source(source).with_checks(!args.no_runtime_checks);
+    let main = codegen.compile_main(&fold::fold(&checked.program)).map_err(|e| vec![e])?;
 
     let x = 0u64;
     let y = 0u64;
@@ -52,3 +367,342 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Runs the front-end on each file, and on the `.x` files of directories
+/// and their subdirectories, reporting all their errors and warnings.
+fn check(args: CheckArgs) -> ExitCode {
+    let levels = args.lints.levels();
+    let mut status = ExitCode::SUCCESS;
+    for path in &args.paths {
+        let paths = match programs(path) {
+            Ok(paths) => paths,
+            Err(e) => {
+                status = io_error(format!("Couldn't read {}: {}", path.display(), e), args.error_format);
+                continue;
+            },
+        };
+        for path in paths {
+            let source = match SourceFile::from_path(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    status = io_error(format!("Couldn't read {}: {}", path.display(), e), args.error_format);
+                    continue;
+                },
+            };
+            let checked = frontend::check(&source.text, &levels);
+            let mut emitter = Emitter::new(&source).with_format(args.error_format);
+            for diagnostic in checked.diagnostics() {
+                emitter.emit(&diagnostic);
+            }
+            emitter.summary();
+            if checked.has_errors() {
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+/// The programs at a path: the file itself, or the `.x` files under the
+/// directory, in order.
+fn programs(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut programs = Vec::new();
+    let mut entries = fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            programs.extend(self::programs(&entry)?);
+        } else if entry.extension().is_some_and(|extension| extension == "x") {
+            programs.push(entry);
+        }
+    }
+    Ok(programs)
+}
+
+/// Runs the tests of a program whose name contains the filter, each of
+/// them until a runtime check fails, and prints which passed.
+fn test(args: TestArgs) -> ExitCode {
+    let source = match SourceFile::from_path(&args.path) {
+        Ok(source) => source,
+        Err(e) => return io_error(format!("Couldn't read {}: {}", args.path.display(), e), args.error_format),
+    };
+    let mut emitter = Emitter::new(&source).with_format(args.error_format);
+    let checked = frontend::check(&source.text, &args.lints.levels());
+    for diagnostic in checked.diagnostics() {
+        emitter.emit(&diagnostic);
+    }
+    emitter.summary();
+    if checked.has_errors() {
+        return ExitCode::FAILURE;
+    }
+
+    let program = fold::fold(&checked.program);
+    let tests = testing::tests(&program, args.filter.as_deref());
+    let filtered = testing::tests(&program, None).len() - tests.len();
+    let context = Context::create();
+    let results = match testing::run(&context, &source, &tests, !args.no_runtime_checks) {
+        Ok(results) => results,
+        Err(error) => {
+            emitter.emit(&error.into());
+            return ExitCode::FAILURE;
+        },
+    };
+
+    println!("running {} test{}", tests.len(), if tests.len() == 1 { "" } else { "s" });
+    for (test, result) in tests.iter().zip(&results) {
+        println!("test {} ... {}", test.name, if result.is_none() { "ok" } else { "FAILED" });
+    }
+    let failures = tests.iter().zip(&results).filter_map(|(test, result)| Some((test, result.as_ref()?))).collect::<Vec<_>>();
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (test, message) in &failures {
+            println!("    {}: {}", test.name, message);
+        }
+    }
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    let passed = tests.len() - failures.len();
+    println!("\ntest result: {}. {} passed; {} failed; {} filtered out", status, passed, failures.len(), filtered);
+    match failures.len() {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+/// How many times `lang fix` applies suggestions, as fixing errors can
+/// uncover more of them.
+const MAX_FIX_ROUNDS: usize = 8;
+
+/// Applies the machine-applicable suggestions for a file, as long as the
+/// fixed program has less errors than the original, then reports what is
+/// left.
+fn fix(args: FixArgs) -> ExitCode {
+    let original = match SourceFile::from_path(&args.path) {
+        Ok(source) => source,
+        Err(e) => return io_error(format!("Couldn't read {}: {}", args.path.display(), e), args.error_format),
+    };
+    let levels = args.lints.levels();
+    let mut text = original.text.clone();
+    let mut checked = frontend::check(&text, &levels);
+    let mut total = 0;
+    let mut rejected = false;
+    for _ in 0..MAX_FIX_ROUNDS {
+        let (fixed, applied) = fix::apply(&text, checked.suggestions());
+        if applied == 0 {
+            break;
+        }
+        let rechecked = frontend::check(&fixed, &levels);
+        if !rechecked.errors.is_empty() && rechecked.errors.len() >= checked.errors.len() {
+            rejected = true;
+            break;
+        }
+        (text, checked, total) = (fixed, rechecked, total + applied);
+    }
+    let source = SourceFile::new(original.name, text);
+    if total > 0 {
+        if let Err(e) = std::fs::write(&args.path, &source.text) {
+            return io_error(format!("Couldn't write {}: {}", args.path.display(), e), args.error_format);
+        }
+        if args.error_format == ErrorFormat::Human {
+            eprintln!("Fixed {} issue{} in {}", total, if total == 1 { "" } else { "s" }, source.name);
+        }
+    }
+    if rejected {
+        let error = CompileError::new(ErrorKind::Parse, format!("Not applying some fixes to {}", source.name))
+            .with_note("the program would have errors which it does not have now")
+            .with_help("apply the fixes suggested below by hand");
+        Emitter::new(&source).with_format(args.error_format).emit(&error.into());
+    }
+    let mut emitter = Emitter::new(&source).with_format(args.error_format);
+    for diagnostic in checked.diagnostics() {
+        emitter.emit(&diagnostic);
+    }
+    emitter.summary();
+    match checked.has_errors() {
+        false => ExitCode::SUCCESS,
+        true => ExitCode::FAILURE,
+    }
+}
+
+/// Formats each file, or with `--check` lists the ones which are not
+/// formatted. Files with syntax errors are left as they are.
+fn fmt(args: FmtArgs) -> ExitCode {
+    let mut status = ExitCode::SUCCESS;
+    for path in &args.paths {
+        let source = match SourceFile::from_path(path) {
+            Ok(source) => source,
+            Err(e) => {
+                status = io_error(format!("Couldn't read {}: {}", path.display(), e), args.error_format);
+                continue;
+            },
+        };
+        let formatted = match format::format(&source.text) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                let mut emitter = Emitter::new(&source).with_format(args.error_format);
+                for error in errors {
+                    emitter.emit(&error.into());
+                }
+                emitter.summary();
+                status = ExitCode::FAILURE;
+                continue;
+            },
+        };
+        if formatted == source.text {
+            continue;
+        }
+        if args.check {
+            println!("Would reformat {}", source.name);
+            status = ExitCode::FAILURE;
+        } else if let Err(e) = fs::write(path, formatted) {
+            status = io_error(format!("Couldn't write {}: {}", path.display(), e), args.error_format);
+        }
+    }
+    status
+}
+
+fn run_lsp() -> ExitCode {
+    match lsp::serve() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => io_error(format!("The language server stopped: {}", e), ErrorFormat::Human),
+    }
+}
+
+/// Entries of the REPL, kept in a file with one JSON string per line so
+/// that entries can span lines.
+struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Loads the history, which is empty if the file doesn't exist yet.
+    fn load(path: Option<PathBuf>) -> Self {
+        let text = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        let entries = text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+        History { entries, path }
+    }
+
+    /// Adds an entry, appending it to the file. Failing to write the file
+    /// is not worth interrupting the session for.
+    fn push(&mut self, entry: &str) {
+        if let Some(path) = &self.path {
+            let file = fs::OpenOptions::new().create(true).append(true).open(path);
+            if let (Ok(mut file), Ok(line)) = (file, serde_json::to_string(entry)) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+        self.entries.push(entry.to_string());
+    }
+
+    /// The entry recalled by `!!`, the last one, or `!N`, the Nth one as
+    /// numbered by `:history`.
+    fn recall(&self, command: &str) -> Option<&String> {
+        match command {
+            "!!" => self.entries.last(),
+            _ => self.entries.get(command.strip_prefix('!')?.parse::<usize>().ok()?.checked_sub(1)?),
+        }
+    }
+}
+
+fn run_repl(args: ReplArgs) -> ExitCode {
+    let context = Context::create();
+    let mut session = match repl::Session::new(&context, args.lints.levels(), !args.no_runtime_checks) {
+        Ok(session) => session,
+        Err(error) => {
+            let none = SourceFile::new("", "");
+            Emitter::new(&none).emit(&error.into());
+            return ExitCode::FAILURE;
+        },
+    };
+    let path = args.history.or_else(|| std::env::home_dir().map(|home| home.join(".lang_history")));
+    let mut history = History::load(path);
+    let interactive = io::stdin().is_terminal();
+    let mut lines = io::stdin().lock().lines();
+    let mut entries = 0;
+    loop {
+        let mut text = String::new();
+        loop {
+            if interactive {
+                print!("{}", if text.is_empty() { "> " } else { "... " });
+                let _ = io::stdout().flush();
+            }
+            let Some(Ok(line)) = lines.next() else {
+                return ExitCode::SUCCESS;
+            };
+            text.push_str(&line);
+            text.push('\n');
+            if repl::is_complete(&text) {
+                break;
+            }
+        }
+        let command = text.trim();
+        match command {
+            "" => continue,
+            ":quit" | ":q" => return ExitCode::SUCCESS,
+            ":history" => {
+                for (i, entry) in history.entries.iter().enumerate() {
+                    println!("{:>4}  {}", i + 1, entry);
+                }
+                continue;
+            },
+            _ => (),
+        }
+        let entry = match command.starts_with('!') {
+            true => match history.recall(command) {
+                Some(entry) => {
+                    println!("{}", entry);
+                    entry.clone()
+                },
+                None => {
+                    eprintln!("error: no entry {} in the history", command);
+                    continue;
+                },
+            },
+            false => command.to_string(),
+        };
+        history.push(&entry);
+        entries += 1;
+        let source = SourceFile::new(format!("<entry {}>", entries), entry);
+        let mut emitter = Emitter::new(&source);
+        match session.eval(&source, &mut emitter) {
+            Ok(Some(value)) => println!("{}", value),
+            Ok(None) => (),
+            Err(errors) => {
+                for error in errors {
+                    emitter.emit(&error.into());
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_recall() {
+        let history = History { entries: vec!["1".to_string(), "let x := 2".to_string()], path: None };
+        assert_eq!(history.recall("!!").unwrap(), "let x := 2");
+        assert_eq!(history.recall("!1").unwrap(), "1");
+        for command in ["!0", "!3", "!x", "!"] {
+            assert_eq!(history.recall(command), None, "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_ice_report() {
+        let source = SourceFile::new("", "");
+        let rendered = anstream::adapter::strip_str(&ice("oops", "src/codegen.rs:1:2").render(&source)).to_string();
+        assert_eq!(
+            rendered,
+            "error: internal compiler error: oops\n \
+             = note: the compiler panicked at src/codegen.rs:1:2\n \
+             = note: this is a bug in the compiler, not in your program\n \
+             = help: please report it, together with the program which triggered it\n",
+        );
+    }
+}
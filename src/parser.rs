@@ -1,57 +1,279 @@
-use crate::tokenizer::Tokenizer;
+use crate::attribute::Attribute;
+use crate::code::ErrorCode;
+use crate::tokenizer::{Keyword, Token, Tokenizer};
+use crate::error::CompileError;
 use crate::expression::Expression;
+use crate::span::Span;
+use crate::testing::Test;
 use core::str;
+use std::io::Read;
 use std::path::Path;
 
-pub struct Parser {
-    pub tokens: Tokenizer,
+/// How many errors are collected before the parser gives up on the rest of
+/// the input.
+pub const DEFAULT_ERROR_LIMIT: usize = 20;
+
+/// How deeply parentheses and blocks may nest. Every stage of the compiler
+/// recurses into them, so deeper programs would overflow the stack.
+pub const MAX_NESTING: usize = 64;
+
+pub struct Parser<'a> {
+    pub tokens: Tokenizer<'a>,
+    /// `#![...]` attributes at the start of the file.
+    pub attributes: Vec<Attribute>,
+    /// Whether an item was parsed, after which inner attributes are no
+    /// longer allowed.
+    started: bool,
+    /// Errors the parser recovered from.
+    errors: Vec<CompileError>,
+    error_limit: usize,
+    limit_reached: bool,
+    /// Span of the item last returned by `next`.
+    span: Span,
+    /// How many parentheses and blocks enclose the current token.
+    nesting: usize,
+}
+
+impl<'a> From<Tokenizer<'a>> for Parser<'a> {
+    fn from(tokens: Tokenizer<'a>) -> Self {
+        Parser {
+            tokens,
+            attributes: Vec::new(),
+            started: false,
+            errors: Vec::new(),
+            error_limit: DEFAULT_ERROR_LIMIT,
+            limit_reached: false,
+            span: Span::default(),
+            nesting: 0,
+        }
+    }
 }
 
-impl TryFrom<&Path> for Parser {
+impl TryFrom<&Path> for Parser<'_> {
     type Error = std::io::Error;
     fn try_from(path: &Path) -> Result<Self, Self::Error> {
-        let tokenizer = Tokenizer::try_from(path)?;
-        Ok(Parser {
-            tokens: tokenizer,
-        })
+        Ok(Tokenizer::try_from(path)?.into())
     }
 }
 
-impl TryFrom<&'static str> for Parser {
-    type Error = std::io::Error;
-    fn try_from(path: &'static str) -> Result<Self, Self::Error> {
-        let tokenizer = Tokenizer::try_from(path)?;
-        Ok(Parser {
-            tokens: tokenizer,
-        })
+impl From<String> for Parser<'_> {
+    fn from(data: String) -> Self {
+        Tokenizer::from(data).into()
+    }
+}
+
+impl<'a> From<&'a str> for Parser<'a> {
+    fn from(data: &'a str) -> Self {
+        Tokenizer::from(data).into()
     }
 }
 
-impl Parser {
-    fn next_of<T: Parsable>(&mut self) -> Result<T, String> {
+impl Parser<'_> {
+    /// Parser over everything `reader` yields until its end, e.g. stdin.
+    pub fn from_reader(reader: impl Read) -> std::io::Result<Self> {
+        Ok(Tokenizer::from_reader(reader)?.into())
+    }
+}
+
+impl Parser<'_> {
+    fn next_of<T: Parsable>(&mut self) -> Result<T, CompileError> {
         T::parse(self)
     }
+
+    /// Parses what the parenthesis or brace at `open` encloses, unless it
+    /// nests deeper than `MAX_NESTING`.
+    pub fn nested<T>(&mut self, open: Span, parse: impl FnOnce(&mut Self) -> Result<T, CompileError>) -> Result<T, CompileError> {
+        if self.nesting >= MAX_NESTING {
+            let message = format!("Parentheses and blocks nested more than {} deep", MAX_NESTING);
+            return Err(CompileError::parse(message, open)
+                .with_code(ErrorCode::NestingTooDeep)
+                .with_help("move the innermost expressions to variables"));
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+
+    pub fn with_error_limit(mut self, error_limit: usize) -> Self {
+        self.error_limit = error_limit.max(1);
+        self
+    }
+
+    /// Records an error to report once parsing is done, so that parsing can
+    /// go on after it.
+    pub fn record(&mut self, error: CompileError) {
+        if self.errors.len() < self.error_limit {
+            self.errors.push(error);
+        } else {
+            self.limit_reached = true;
+        }
+    }
+
+    /// Skips tokens until a point where parsing can resume after an error:
+    /// before a `;`, a `}` closing the current block, or a keyword starting
+    /// a statement. Once too many errors were met, skips the whole input.
+    pub fn synchronize(&mut self) {
+        let mut depth = 0usize;
+        while let Some(token) = self.tokens.peek() {
+            match token {
+                _ if self.limit_reached => (),
+                Token::Operator(';') if depth == 0 => return,
+                Token::Operator('}') if depth == 0 => return,
+                Token::Keyword(k) if depth == 0 && Parser::starts_statement(*k) => return,
+                Token::Operator('{') => depth += 1,
+                Token::Operator('}') => depth -= 1,
+                _ => (),
+            }
+            self.tokens.next();
+        }
+    }
+
+    /// Span of the item last returned by `next`, without its attributes.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    fn starts_statement(keyword: Keyword) -> bool {
+        matches!(
+            keyword,
+            Keyword::Let
+                | Keyword::Return
+                | Keyword::Assert
+                | Keyword::Test
+                | Keyword::Fn
+                | Keyword::If
+                | Keyword::While
+                | Keyword::For
+                | Keyword::Loop,
+        )
+    }
+
+    /// Lexical errors and the errors the parser recovered from, in the order
+    /// they appear in the source.
+    pub fn take_errors(&mut self) -> Vec<CompileError> {
+        let mut errors = self
+            .tokens
+            .take_errors()
+            .into_iter()
+            .map(|(error, span)| error.into_error(span))
+            .chain(std::mem::take(&mut self.errors))
+            .collect::<Vec<_>>();
+        errors.sort_by_key(|e| e.span.map(|span| span.start));
+        if self.limit_reached {
+            if let Some(last) = errors.last_mut() {
+                last.notes.push(format!("stopping after {} errors", self.error_limit));
+            }
+        }
+        errors
+    }
 }
 
-impl Iterator for Parser {
-    type Item = Result<Ast, String>;
+impl Iterator for Parser<'_> {
+    /// The next top-level item, or all the errors met while parsing it.
+    type Item = Result<Ast, Vec<CompileError>>;
     fn next(&mut self) -> Option<Self::Item> {
-        fn nexxt(this: &mut Parser) -> <Parser as IntoIterator>::Item {
-            Ok(Ast::Expression(this.next_of()?))
+        while self.tokens.peek() == Some(&Token::Operator('#')) {
+            match self.next_of::<Attribute>() {
+                Ok(attribute) if attribute.inner && !self.started => self.attributes.push(attribute),
+                Ok(attribute) => self.record(
+                    CompileError::parse(
+                        "Attributes are only allowed at the start of a file or on lines of blocks",
+                        attribute.span,
+                    )
+                    .with_code(ErrorCode::MisplacedAttribute),
+                ),
+                Err(error) => {
+                    self.record(error);
+                    self.synchronize();
+                },
+            }
         }
-
-        match self.tokens.peek() {
-            Some(_) => Some(nexxt(self)),
-            _ => None,
+        self.started = true;
+        let start = self.tokens.peek_span();
+        let parsed = match self.tokens.peek() {
+            Some(Token::Keyword(Keyword::Test)) => Some(self.next_of().map(Ast::Test)),
+            Some(_) => Some(self.next_of().map(Ast::Expression)),
+            None => None,
+        };
+        let item = match parsed {
+            Some(Ok(ast)) => {
+                self.span = start.to(self.tokens.span());
+                Some(ast)
+            },
+            Some(Err(error)) => {
+                self.record(error);
+                self.synchronize();
+                if matches!(self.tokens.peek(), Some(Token::Operator(';' | '}'))) {
+                    self.tokens.next();
+                }
+                None
+            },
+            None => None,
+        };
+        let errors = self.take_errors();
+        match item {
+            _ if !errors.is_empty() => Some(Err(errors)),
+            Some(ast) => Some(Ok(ast)),
+            None => None,
         }
     }
 }
 
 pub trait Parsable: Sized {
-    fn parse(parser: &mut Parser) -> Result<Self, String>;
+    fn parse(parser: &mut Parser) -> Result<Self, CompileError>;
 }
 
 #[derive(Debug)]
 pub enum Ast {
     Expression(Expression),
+    Test(Test),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(data: &str, limit: usize) -> Vec<CompileError> {
+        Parser::from(data)
+            .with_error_limit(limit)
+            .filter_map(Result::err)
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn test_report_all_errors() {
+        let errors = errors("{ 1 +; } + { let := 2; 3 }; (4 *", DEFAULT_ERROR_LIMIT);
+        assert_eq!(errors.len(), 4, "unexpected errors: {:?}", errors);
+        assert!(errors.windows(2).all(|w| w[0].span.unwrap().start <= w[1].span.unwrap().start));
+    }
+
+    #[test]
+    fn test_resume_after_error() {
+        let mut parser = Parser::from("1 + ; 2 + 3");
+        assert!(matches!(parser.next(), Some(Err(_))));
+        assert!(matches!(parser.next(), Some(Ok(Ast::Expression(Expression::Add(..))))));
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_error_limit() {
+        let errors = errors("{ +; +; +; +; +; }", 2);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[1].notes.iter().any(|note| note.contains("stopping after 2 errors")));
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |open: &str, close: &str, depth: usize| format!("{}1{}", open.repeat(depth), close.repeat(depth));
+        assert!(errors(&nested("(", ")", MAX_NESTING), DEFAULT_ERROR_LIMIT).is_empty());
+        assert!(errors(&nested("{ ", " }", MAX_NESTING), DEFAULT_ERROR_LIMIT).is_empty());
+        // Operators chained without parentheses don't nest.
+        assert!(errors(&format!("1{}", " - 1 * 2".repeat(1000)), DEFAULT_ERROR_LIMIT).is_empty());
+        for (open, close) in [("(", ")"), ("{ (", ") }"), ("{", "")] {
+            let errors = errors(&nested(open, close, 4000), DEFAULT_ERROR_LIMIT);
+            assert_eq!(errors[0].code, Some(ErrorCode::NestingTooDeep), "{}: {:?}", open, errors);
+        }
+    }
 }
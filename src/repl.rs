@@ -0,0 +1,150 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use inkwell::context::Context;
+
+use crate::block::Line;
+use crate::codegen::{CodeGen, Global};
+use crate::diagnostic::Emitter;
+use crate::error::{CompileError, ErrorKind};
+use crate::{fold, frontend};
+use crate::lint::LintLevels;
+use crate::runtime;
+use crate::source::SourceFile;
+use crate::tokenizer::{Token, Tokenizer};
+use crate::typeck::Type;
+use crate::value::Value;
+
+/// Memory in which a compiled entry stores a value.
+struct Slot {
+    type_: Type,
+    bits: Box<Cell<u64>>,
+}
+
+impl Slot {
+    fn new(type_: Type) -> Self {
+        Slot { type_, bits: Box::new(Cell::new(0)) }
+    }
+
+    fn global(&self, name: &str) -> Global {
+        Global { name: name.to_string(), type_: self.type_, address: self.bits.as_ptr() as usize }
+    }
+
+    fn value(&self) -> Value {
+        Value::from_bits(self.type_, self.bits.get())
+    }
+}
+
+/// A REPL session: each entry is compiled into a function of its own and
+/// run, and the variables it defines stay available to the next entries.
+pub struct Session<'ctx> {
+    codegen: CodeGen<'ctx>,
+    levels: LintLevels,
+    /// Variables defined by the entries so far.
+    variables: HashMap<String, Value>,
+}
+
+impl<'ctx> Session<'ctx> {
+    pub fn new(context: &'ctx Context, levels: LintLevels, checks: bool) -> Result<Self, CompileError> {
+        let codegen = CodeGen::new(context)?.with_checks(checks).with_recoverable_traps();
+        Ok(Session { codegen, levels, variables: HashMap::new() })
+    }
+
+    /// Checks, compiles and runs an entry, emitting its warnings, and returns
+    /// its value if it has one. Errors are returned unless they were emitted
+    /// already; the variables of entries with errors are not kept.
+    pub fn eval(&mut self, source: &SourceFile, emitter: &mut Emitter) -> Result<Option<Value>, Vec<CompileError>> {
+        let types = self.variables.iter().map(|(name, value)| (name.clone(), value.type_())).collect();
+        let checked = frontend::check_entry(&source.text, &self.levels, &types);
+        if !checked.errors.is_empty() {
+            return Err(checked.errors);
+        }
+        for diagnostic in &checked.lints {
+            emitter.emit(diagnostic);
+        }
+        if emitter.errors > 0 {
+            return Ok(None);
+        }
+
+        let mut defined = HashMap::new();
+        for line in &checked.block.lines {
+            if let Line::LetStatement { name, .. } = line {
+                defined.insert(name.clone(), Slot::new(checked.variables[name]));
+            }
+        }
+        let result = checked.type_.map(Slot::new);
+        let inputs = self.variables.iter().map(|(name, value)| (name.clone(), *value)).collect::<Vec<_>>();
+        let outputs = defined.iter().map(|(name, slot)| slot.global(name)).collect::<Vec<_>>();
+        let result_global = result.as_ref().map(|slot| slot.global("result"));
+        self.codegen.set_source(source);
+        let entry = self
+            .codegen
+            .compile_entry(&fold::fold_entry(&checked.block), &inputs, &outputs, result_global.as_ref())
+            .map_err(|e| vec![e])?;
+        unsafe { entry.call(0, 0) };
+
+        if let Some(message) = runtime::take_trap() {
+            return Err(vec![CompileError::new(ErrorKind::Runtime, message)]);
+        }
+        self.variables.extend(defined.iter().map(|(name, slot)| (name.clone(), slot.value())));
+        Ok(result.map(|slot| slot.value()))
+    }
+}
+
+/// Whether an entry is complete, or it has unclosed braces or parentheses
+/// and goes on on the next line.
+pub fn is_complete(text: &str) -> bool {
+    let depth = Tokenizer::from(text).fold(0isize, |depth, token| match token {
+        Token::Operator('{' | '(') => depth + 1,
+        Token::Operator('}' | ')') => depth - 1,
+        _ => depth,
+    });
+    depth <= 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_all(entries: &[&str]) -> Vec<Result<Option<Value>, Vec<CompileError>>> {
+        let context = Context::create();
+        let mut session = Session::new(&context, LintLevels::default(), true).unwrap();
+        entries
+            .iter()
+            .map(|entry| {
+                let source = SourceFile::new("<entry>", *entry);
+                session.eval(&source, &mut Emitter::new(&source))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_persistent_variables() {
+        let results = eval_all(&["let x := 4", "let y := x * x; y + 1", "{ let x := 1; x } + y", "let x := 7 as i64", "x / (2 as i64)"]);
+        assert_eq!(results[0], Ok(None));
+        assert_eq!(results[1], Ok(Some(Value::F64(17.))));
+        assert_eq!(results[2], Ok(Some(Value::F64(17.))));
+        assert_eq!(results[4], Ok(Some(Value::I64(3))));
+    }
+
+    #[test]
+    fn test_errors_do_not_define_variables() {
+        let results = eval_all(&["let x := 1; y", "x", "let z := 0 as i64", "let w := 1 as i64 / z", "w"]);
+        assert!(results[0].is_err());
+        assert_eq!(results[1].as_ref().unwrap_err()[0].message, "Cannot find variable 'x' in this scope");
+        // The variables of previous entries are not constants, so this fails
+        // at runtime rather than at compile time.
+        assert_eq!(results[3].as_ref().unwrap_err()[0].message, "division by zero at <entry>:1:19");
+        assert!(results[4].is_err());
+    }
+
+    #[test]
+    fn test_complete_entries() {
+        assert!(is_complete("1 + 2"));
+        assert!(is_complete("let x := { 1 }"));
+        assert!(!is_complete("let x := {"));
+        assert!(!is_complete("(1 +"));
+        // Extra closing braces are errors to report, not more lines to read.
+        assert!(is_complete("}"));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::block::{Block, Line};
+use crate::code::ErrorCode;
+use crate::error::{Applicability, CompileError};
+use crate::expression::Expression;
+use crate::parser::Ast;
+use crate::span::Span;
+use crate::suggest;
+
+/// Checks that every variable of a program refers to a `let` binding in
+/// scope, suggesting a similar name for those which don't.
+pub fn check(program: &[Ast]) -> Vec<CompileError> {
+    resolve(program).errors
+}
+
+/// The variables of a program which refer to a `let` binding, by their
+/// span and the span of the name in the binding.
+pub fn definitions(program: &[Ast]) -> Vec<(Span, Span)> {
+    resolve(program).definitions
+}
+
+fn resolve(program: &[Ast]) -> Resolver {
+    let mut resolver = Resolver { scopes: vec![Vec::new()], errors: Vec::new(), definitions: Vec::new() };
+    for ast in program {
+        match ast {
+            Ast::Expression(expression) => resolver.expression(expression),
+            Ast::Test(test) => resolver.block(&test.body),
+        }
+    }
+    resolver
+}
+
+/// Checks an entry of the REPL, in which `variables` of the previous
+/// entries are in scope.
+pub fn check_entry(block: &Block, variables: impl IntoIterator<Item = String>) -> Vec<CompileError> {
+    let variables = variables.into_iter().map(|name| (name, None)).collect();
+    let mut resolver = Resolver { scopes: vec![variables], errors: Vec::new(), definitions: Vec::new() };
+    resolver.lines(block);
+    resolver.errors
+}
+
+struct Resolver {
+    /// Names bound in each block being checked, innermost last, with the
+    /// span of the name in their `let` unless they come from a previous
+    /// entry of the REPL.
+    scopes: Vec<Vec<(String, Option<Span>)>>,
+    errors: Vec<CompileError>,
+    definitions: Vec<(Span, Span)>,
+}
+
+impl Resolver {
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(Vec::new());
+        self.lines(block);
+        self.scopes.pop();
+    }
+
+    fn lines(&mut self, block: &Block) {
+        for line in &block.lines {
+            match line {
+                Line::Expression(expression) | Line::ReturnStatement(expression) => self.expression(expression),
+                Line::LetStatement { name, span, value, .. } => {
+                    self.expression(value);
+                    self.scopes.last_mut().expect("a block has a scope").push((name.clone(), Some(*span)));
+                },
+                Line::Assert { left, right, .. } => {
+                    self.expression(left);
+                    self.expression(right);
+                },
+                Line::Error(_) => (),
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Literal(..) => (),
+            Expression::Variable(name, span) => {
+                // The last binding of a name shadows the previous ones.
+                match self.scopes.iter().flatten().rev().find(|(bound, _)| bound == name) {
+                    Some((_, Some(definition))) => self.definitions.push((*span, *definition)),
+                    Some((_, None)) => (),
+                    None => self.errors.push(self.unknown(name, *span)),
+                }
+            },
+            Expression::Block(block) => self.block(block),
+            Expression::Add(a, b, _) | Expression::Sub(a, b, _) | Expression::Mul(a, b, _) | Expression::Div(a, b, _) => {
+                self.expression(a);
+                self.expression(b);
+            },
+            Expression::Cast(value, _, _) => self.expression(value),
+        }
+    }
+
+    fn unknown(&self, name: &str, span: Span) -> CompileError {
+        let error = CompileError::resolve(format!("Cannot find variable '{}' in this scope", name), span)
+            .with_code(ErrorCode::UnknownVariable);
+        let variables = self.scopes.iter().flatten().map(|(name, _)| name.as_str());
+        if let Some(variable) = suggest::closest(name, variables) {
+            return error.with_suggestion(span, variable, "a variable with a similar name exists", Applicability::MaybeIncorrect);
+        }
+        match suggest::keyword(name) {
+            Some(keyword) => {
+                error.with_suggestion(span, keyword.as_str(), "there is a keyword with a similar name", Applicability::MaybeIncorrect)
+            },
+            None => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn check_str(data: &str) -> Vec<CompileError> {
+        let program = Parser::from(data).collect::<Result<Vec<_>, _>>().expect("test programs are valid");
+        check(&program)
+    }
+
+    #[test]
+    fn test_unknown_variables() {
+        let errors = check_str("{ let count := 1; let x := cuont; { let y := 2; y } + y + x }");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "Cannot find variable 'cuont' in this scope");
+        assert_eq!(errors[0].suggestions[0].replacement, "count");
+        assert_eq!(errors[1].span, Some(Span::new(54, 55)));
+        // `y` is out of scope there, `x` is the closest name in scope.
+        assert_eq!(errors[1].suggestions[0].replacement, "x");
+    }
+
+    #[test]
+    fn test_misspelled_keyword() {
+        let errors = check_str("retrun");
+        assert_eq!(errors[0].suggestions[0].message, "there is a keyword with a similar name");
+        assert_eq!(errors[0].suggestions[0].replacement, "return");
+    }
+
+    #[test]
+    fn test_definitions() {
+        let program = Parser::from("{ let x := 1; let x := x; { let y := x; y } }").collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            definitions(&program),
+            [
+                (Span::new(23, 24), Span::new(6, 7)),
+                (Span::new(37, 38), Span::new(18, 19)),
+                (Span::new(40, 41), Span::new(32, 33)),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_variable_before_its_let() {
+        assert_eq!(check_str("{ let x := x; 1 }").len(), 1);
+        assert!(check_str("{ let x := 1; let x := x; x }").is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+//! Routines which compiled programs call.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr};
+
+/// Exit status of a program stopped by a failed runtime check, e.g. a
+/// division by zero. Compile errors exit with 1 and compiler panics with 101.
+pub const TRAP_EXIT_CODE: i32 = 3;
+
+/// Reports a failed runtime check, such as "division by zero at file.x:3:7",
+/// and exits with `TRAP_EXIT_CODE`.
+///
+/// # Safety
+///
+/// `message` must point to a NUL-terminated string.
+pub unsafe extern "C" fn trap(message: *const c_char) -> ! {
+    let message = unsafe { CStr::from_ptr(message) };
+    eprintln!("runtime error: {}", message.to_string_lossy());
+    std::process::exit(TRAP_EXIT_CODE)
+}
+
+thread_local! {
+    /// The message of the last failed check recorded by `record_trap`.
+    static TRAP: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Records a failed runtime check for `take_trap`, for code compiled with
+/// recoverable traps which returns after calling it.
+///
+/// # Safety
+///
+/// `message` must point to a NUL-terminated string.
+pub unsafe extern "C" fn record_trap(message: *const c_char) {
+    let message = unsafe { CStr::from_ptr(message) };
+    TRAP.with(|trap| *trap.borrow_mut() = Some(message.to_string_lossy().into_owned()));
+}
+
+/// The message of the check which failed since the last call, if any.
+pub fn take_trap() -> Option<String> {
+    TRAP.with(|trap| trap.borrow_mut().take())
+}
@@ -0,0 +1,88 @@
+use std::io::{self, Read};
+use std::path::Path;
+use std::{fs, ops::Range};
+
+use crate::span::Span;
+
+/// A program's text together with the name it is reported under, and the
+/// position of its lines to turn spans into line and column numbers.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    /// Byte offsets at which each line starts.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        let text = text.into();
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile { name: name.into(), text, line_starts }
+    }
+
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        Ok(SourceFile::new(path.display().to_string(), fs::read_to_string(path)?))
+    }
+
+    pub fn from_reader(name: impl Into<String>, mut reader: impl Read) -> io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Ok(SourceFile::new(name, text))
+    }
+
+    /// Zero-based index of the line containing a byte offset.
+    pub fn line_index(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// One-based line and column of a byte offset. Columns count
+    /// characters, not bytes.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_index(offset);
+        let column = self.text[self.line_starts[line]..offset].chars().count();
+        (line + 1, column + 1)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Byte range of a zero-based line, without its line terminator.
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line];
+        let end = self.line_starts.get(line + 1).map_or(self.text.len(), |&next| next - 1);
+        let end = if self.text[start..end].ends_with('\r') { end - 1 } else { end };
+        start..end
+    }
+
+    /// Text of a zero-based line, without its line terminator.
+    pub fn line(&self, line: usize) -> &str {
+        &self.text[self.line_range(line)]
+    }
+
+    pub fn slice(&self, span: Span) -> &str {
+        &self.text[span.start..span.end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_col() {
+        let source = SourceFile::new("test.x", "ab\ncd\r\n\né");
+        assert_eq!(source.line_col(0), (1, 1));
+        assert_eq!(source.line_col(1), (1, 2));
+        assert_eq!(source.line_col(3), (2, 1));
+        assert_eq!(source.line_col(8), (4, 1));
+        assert_eq!(source.line_col(10), (4, 2));
+        assert_eq!(source.line(1), "cd");
+        assert_eq!(source.line(2), "");
+        assert_eq!(source.line_count(), 4);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::tokenizer::Keyword;
+
+/// The candidate closest to `name`, if one is close enough to be a likely
+/// typo of it: same letters in another case, or few edits away.
+pub fn closest<'c>(name: &str, candidates: impl IntoIterator<Item = &'c str>) -> Option<&'c str> {
+    // One edit for every three characters, so that short names don't match
+    // everything.
+    let limit = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| match candidate.to_lowercase() == name.to_lowercase() {
+            true => (0, candidate),
+            false => (strsim::damerau_levenshtein(name, candidate), candidate),
+        })
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The keyword `name` is probably a typo of, e.g. `return` for `retrun`.
+pub fn keyword(name: &str) -> Option<Keyword> {
+    closest(name, Keyword::ALL.iter().map(Keyword::as_str)).and_then(Keyword::lookup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest() {
+        let names = ["count", "total", "x"];
+        assert_eq!(closest("cuont", names), Some("count"));
+        assert_eq!(closest("Total", names), Some("total"));
+        assert_eq!(closest("totl", names), Some("total"));
+        assert_eq!(closest("y", names), Some("x"));
+        assert_eq!(closest("width", names), None);
+        assert_eq!(closest("x", names), None);
+    }
+
+    #[test]
+    fn test_keyword() {
+        assert_eq!(keyword("retrun"), Some(Keyword::Return));
+        assert_eq!(keyword("Let"), Some(Keyword::Let));
+        assert_eq!(keyword("whiel"), Some(Keyword::While));
+        assert_eq!(keyword("value"), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Tests written in programs as `test "name" { ... }` items, which only
+//! `lang test` runs.
+
+use inkwell::context::Context;
+
+use crate::block::Block;
+use crate::codegen::CodeGen;
+use crate::error::CompileError;
+use crate::parser::{Ast, Parsable, Parser};
+use crate::runtime;
+use crate::source::SourceFile;
+use crate::span::Span;
+use crate::tokenizer::Keyword;
+
+/// `test "name" { ... }`, which passes unless a runtime check fails while
+/// running its body, such as an `assert`.
+#[derive(Debug, Clone)]
+pub struct Test {
+    pub name: String,
+    pub name_span: Span,
+    pub body: Block,
+    /// Span of the whole test, from `test` to the end of its body.
+    pub span: Span,
+}
+
+impl Parsable for Test {
+    fn parse(parser: &mut Parser) -> Result<Self, CompileError> {
+        parser.tokens.expect_keyword(Keyword::Test)?;
+        let start = parser.tokens.span();
+        let name = parser.tokens.expect_string()?;
+        let name_span = parser.tokens.span();
+        let body = Block::parse(parser)?;
+        let span = start.to(body.span);
+        Ok(Test { name, name_span, body, span })
+    }
+}
+
+/// The tests of a program whose name contains `filter`, in order.
+pub fn tests<'a>(program: &'a [Ast], filter: Option<&str>) -> Vec<&'a Test> {
+    program
+        .iter()
+        .filter_map(|ast| match ast {
+            Ast::Test(test) => Some(test),
+            Ast::Expression(_) => None,
+        })
+        .filter(|test| filter.is_none_or(|filter| test.name.contains(filter)))
+        .collect()
+}
+
+/// Compiles tests which passed the front-end into one module and runs each
+/// of them, returning the message of the check which failed for those which
+/// did not pass.
+pub fn run(context: &Context, source: &SourceFile, tests: &[&Test], checks: bool) -> Result<Vec<Option<String>>, CompileError> {
+    let mut codegen = CodeGen::new(context)?.with_source(source).with_checks(checks).with_recoverable_traps();
+    let bodies = tests.iter().map(|test| &test.body).collect::<Vec<_>>();
+    let functions = codegen.compile_tests(&bodies)?;
+    runtime::take_trap();
+    Ok(functions
+        .iter()
+        .map(|function| {
+            unsafe { function.call(0, 0) };
+            runtime::take_trap()
+        })
+        .collect())
+}
+
+/// The message of a failed `assert` of the condition at `span`, without
+/// where it is.
+pub fn assertion_failed(source: Option<&SourceFile>, span: Span) -> String {
+    match source.and_then(|source| source.text.get(span.start..span.end)) {
+        Some(condition) => format!("assertion failed: {}", condition),
+        None => "assertion failed".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(data: &str) -> Vec<Ast> {
+        Parser::from(data).collect::<Result<Vec<_>, _>>().expect("test programs are valid")
+    }
+
+    #[test]
+    fn test_parse_tests() {
+        let program = program("1 + 1\ntest \"addition\" { assert(1 + 1 == 2); }\ntest \"sub\" { 2 }");
+        let names = tests(&program, None).iter().map(|test| test.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["addition", "sub"]);
+        assert_eq!(tests(&program, None)[0].name_span, Span::new(11, 21));
+        assert_eq!(tests(&program, Some("add")).len(), 1);
+        assert!(Parser::from("test addition { }").collect::<Result<Vec<_>, _>>().is_err());
+    }
+
+    #[test]
+    fn test_run_tests() {
+        let source = SourceFile::new(
+            "test.x",
+            "test \"ok\" { let x := 2 as i64; assert(x * x == 4 as i64) }\n\
+             test \"assert\" { assert(1 / 10 + 2 / 10 == 3 / 10); }\n\
+             test \"trap\" { let zero := 0 as i64; 1 as i64 / zero }\n\
+             test \"nan\" { assert(0 / 0 != 0 / 0); assert(0 / 0 >= 0 / 0) }",
+        );
+        let program = program(&source.text);
+        let context = Context::create();
+        let results = run(&context, &source, &tests(&program, None), true).unwrap();
+        assert_eq!(
+            results,
+            [
+                None,
+                Some("assertion failed: 1 / 10 + 2 / 10 == 3 / 10 at test.x:2:24".to_string()),
+                Some("division by zero at test.x:3:46".to_string()),
+                Some("assertion failed: 0 / 0 >= 0 / 0 at test.x:4:45".to_string()),
+            ]
+        );
+    }
+}
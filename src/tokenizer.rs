@@ -1,156 +1,543 @@
-use std::{fmt::{self, Display, Formatter}, fs::File, io::{BufRead, BufReader}, iter::Peekable, path::Path};
+use std::{borrow::Cow, fmt::{self, Display, Formatter}, fs, io::Read, path::Path};
 
+use unicode_ident::{is_xid_continue, is_xid_start};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
 
-pub struct Tokenizer {
-    inner: Peekable<TokenizerInner>,
+use crate::code::ErrorCode;
+use crate::error::{Applicability, CompileError};
+use crate::interner::{Interner, Symbol};
+use crate::span::Span;
+
+
+/// Lexer working directly on the bytes of the source. Tokens don't own any
+/// text: numbers are read back from the source through their span and
+/// identifiers are interned.
+pub struct Tokenizer<'a> {
+    source: Cow<'a, str>,
+    /// Byte offset of the next character.
+    pos: usize,
+    peeked: Option<Option<(Token, Span)>>,
+    span: Span,
+    errors: Vec<(LexError, Span)>,
+    /// Spans of the `//` comments met so far.
+    comments: Vec<Span>,
+    interner: Interner,
 }
 
-struct TokenizerInner {
-    chars: Peekable<Box<dyn Iterator<Item=char>>>,
+impl<'a> From<Cow<'a, str>> for Tokenizer<'a> {
+    fn from(source: Cow<'a, str>) -> Self {
+        Tokenizer {
+            source,
+            pos: 0,
+            peeked: None,
+            span: Span::default(),
+            errors: Vec::new(),
+            comments: Vec::new(),
+            interner: Interner::default(),
+        }
+    }
 }
 
-impl TryFrom<&Path> for TokenizerInner {
+impl TryFrom<&Path> for Tokenizer<'_> {
     type Error = std::io::Error;
     fn try_from(path: &Path) -> Result<Self, Self::Error> {
-        let file = File::open(path)?;
-        let iterator = BufReader::new(file).lines();
-        let iterator = iterator.map(|line| line.unwrap().chars().collect::<Vec<_>>()).flatten();
-        let iterator: Box<dyn Iterator<Item = char>> = Box::new(iterator);
-        Ok(TokenizerInner{chars: iterator.peekable()})
+        Ok(fs::read_to_string(path)?.into())
     }
 }
 
-impl TryFrom<&'static str> for TokenizerInner {
-    type Error = std::io::Error;
-    fn try_from(data: &'static str) -> Result<Self, Self::Error> {
-        let iterator = data.chars();
-        let iterator: Box<dyn Iterator<Item = char>> = Box::new(iterator);
-        Ok(TokenizerInner{chars: iterator.peekable()})
+impl From<String> for Tokenizer<'_> {
+    fn from(data: String) -> Self {
+        Cow::<str>::Owned(data).into()
     }
 }
 
-impl TryFrom<&Path> for Tokenizer {
-    type Error = std::io::Error;
-    fn try_from(path: &Path) -> Result<Self, Self::Error> {
-        let inner = TokenizerInner::try_from(path)?;
-        Ok(Tokenizer { inner: inner.peekable() })
+impl<'a> From<&'a str> for Tokenizer<'a> {
+    fn from(data: &'a str) -> Self {
+        Cow::Borrowed(data).into()
     }
 }
 
-impl TryFrom<&'static str> for Tokenizer {
-    type Error = std::io::Error;
-    fn try_from(data: &'static str) -> Result<Self, Self::Error> {
-        let inner = TokenizerInner::try_from(data)?;
-        Ok(Tokenizer { inner: inner.peekable() })
+impl Tokenizer<'_> {
+    /// Tokenizer over everything `reader` yields until its end, e.g. stdin.
+    pub fn from_reader(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut data = String::new();
+        reader.read_to_string(&mut data)?;
+        Ok(data.into())
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// A token. The text it was read from is available through its span, see
+/// `Tokenizer::text`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Token{
-    Number(String),
+    Number,
     Operator(char),
-    Symbol(String),
+    Symbol(Symbol),
+    Keyword(Keyword),
+    /// `"..."`, the name of a test. Its text is available through its span.
+    String,
+    /// A piece of input which could not be tokenized, with the reason why.
+    Error(LexError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexError {
+    NumberFollowedByLetter,
+    /// A character looking like an operator, as an index into `CONFUSABLES`.
+    Confusable(usize),
+    InvalidCharacter(char),
+    /// A string without its closing `"` on the same line.
+    UnterminatedString,
+}
+
+impl LexError {
+    pub fn into_error(self, span: Span) -> CompileError {
+        let error = CompileError::lex(self.to_string(), span);
+        match self {
+            LexError::NumberFollowedByLetter => error.with_code(ErrorCode::InvalidNumber),
+            LexError::InvalidCharacter(_) => error.with_code(ErrorCode::InvalidCharacter),
+            LexError::UnterminatedString => error.with_code(ErrorCode::UnclosedDelimiter),
+            LexError::Confusable(i) => {
+                let (_, _, ascii, ascii_name) = CONFUSABLES[i];
+                let message = format!("use the ASCII {} instead", ascii_name);
+                error
+                    .with_code(ErrorCode::ConfusableCharacter)
+                    .with_suggestion(span, ascii.to_string(), message, Applicability::MachineApplicable)
+            },
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LexError::NumberFollowedByLetter => write!(f, "Number cannot be followed by a letter"),
+            LexError::Confusable(i) => {
+                let (c, name, ascii, ascii_name) = CONFUSABLES[*i];
+                write!(
+                    f,
+                    "Unicode character '{}' (U+{:04X}, {}) looks like '{}' ({}), but it is not",
+                    c, c as u32, name, ascii, ascii_name,
+                )
+            },
+            LexError::InvalidCharacter(c) => write!(f, "Invalid character '{}' (U+{:04X}) in input", c, *c as u32),
+            LexError::UnterminatedString => write!(f, "Unterminated string"),
+        }
+    }
+}
+
+/// Reserved words of the language. Some of them are not used by the parser
+/// yet but are reserved already so that programs don't start using them as
+/// variable names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyword {
+    Let,
+    Return,
+    Fn,
+    If,
+    Else,
+    While,
+    For,
+    Loop,
+    Break,
+    Continue,
+    True,
+    False,
+    As,
+    Test,
+    Assert,
+}
+
+impl Keyword {
+    pub const ALL: [Keyword; 15] = [
+        Keyword::Let,
+        Keyword::Return,
+        Keyword::Fn,
+        Keyword::If,
+        Keyword::Else,
+        Keyword::While,
+        Keyword::For,
+        Keyword::Loop,
+        Keyword::Break,
+        Keyword::Continue,
+        Keyword::True,
+        Keyword::False,
+        Keyword::As,
+        Keyword::Test,
+        Keyword::Assert,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Let => "let",
+            Keyword::Return => "return",
+            Keyword::Fn => "fn",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::For => "for",
+            Keyword::Loop => "loop",
+            Keyword::Break => "break",
+            Keyword::Continue => "continue",
+            Keyword::True => "true",
+            Keyword::False => "false",
+            Keyword::As => "as",
+            Keyword::Test => "test",
+            Keyword::Assert => "assert",
+        }
+    }
+
+    pub fn lookup(s: &str) -> Option<Keyword> {
+        Some(match s {
+            "let" => Keyword::Let,
+            "return" => Keyword::Return,
+            "fn" => Keyword::Fn,
+            "if" => Keyword::If,
+            "else" => Keyword::Else,
+            "while" => Keyword::While,
+            "for" => Keyword::For,
+            "loop" => Keyword::Loop,
+            "break" => Keyword::Break,
+            "continue" => Keyword::Continue,
+            "true" => Keyword::True,
+            "false" => Keyword::False,
+            "as" => Keyword::As,
+            "test" => Keyword::Test,
+            "assert" => Keyword::Assert,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            Token::Number(n) => write!(f, "Number({})", n),
+            Token::Number => write!(f, "Number"),
             Token::Operator(c) => write!(f, "Operator({})", c),
-            Token::Symbol(s) => write!(f, "Symbol({})", s),
+            Token::Symbol(_) => write!(f, "Symbol"),
+            Token::Keyword(k) => write!(f, "Keyword({})", k),
+            Token::String => write!(f, "String"),
+            Token::Error(e) => write!(f, "Error({})", e),
         }
     }
 }
 
-impl TokenizerInner {
-    fn next_number(&mut self) -> Result<Token, String> {
-        let mut str = String::new();
-        while let Some(c) = self.chars.peek() {
-            if !c.is_numeric() { break; }
-            str.push(*c);
-            self.chars.next();
+/// Characters which look like one of our operators but are not, together with
+/// the ASCII character they are mistaken for and the Unicode name of both.
+const CONFUSABLES: &[(char, &str, char, &str)] = &[
+    ('\u{037E}', "Greek Question Mark", ';', "Semicolon"),
+    ('\u{FF1B}', "Fullwidth Semicolon", ';', "Semicolon"),
+    ('\u{FF1A}', "Fullwidth Colon", ':', "Colon"),
+    ('\u{2236}', "Ratio", ':', "Colon"),
+    ('\u{FF1D}', "Fullwidth Equals Sign", '=', "Equals Sign"),
+    ('\u{FF0B}', "Fullwidth Plus Sign", '+', "Plus Sign"),
+    ('\u{2212}', "Minus Sign", '-', "Hyphen-Minus"),
+    ('\u{2010}', "Hyphen", '-', "Hyphen-Minus"),
+    ('\u{2013}', "En Dash", '-', "Hyphen-Minus"),
+    ('\u{2014}', "Em Dash", '-', "Hyphen-Minus"),
+    ('\u{FF0D}', "Fullwidth Hyphen-Minus", '-', "Hyphen-Minus"),
+    ('\u{00D7}', "Multiplication Sign", '*', "Asterisk"),
+    ('\u{2217}', "Asterisk Operator", '*', "Asterisk"),
+    ('\u{FF0A}', "Fullwidth Asterisk", '*', "Asterisk"),
+    ('\u{00F7}', "Division Sign", '/', "Slash"),
+    ('\u{2215}', "Division Slash", '/', "Slash"),
+    ('\u{FF0F}', "Fullwidth Solidus", '/', "Slash"),
+    ('\u{FF08}', "Fullwidth Left Parenthesis", '(', "Left Parenthesis"),
+    ('\u{FF09}', "Fullwidth Right Parenthesis", ')', "Right Parenthesis"),
+    ('\u{FF5B}', "Fullwidth Left Curly Bracket", '{', "Left Curly Brace"),
+    ('\u{FF5D}', "Fullwidth Right Curly Bracket", '}', "Right Curly Brace"),
+    ('\u{200B}', "Zero Width Space", ' ', "Space"),
+    ('\u{FEFF}', "Zero Width No-Break Space", ' ', "Space"),
+];
+
+fn is_symbol_start(c: char) -> bool {
+    c == '_' || is_xid_start(c)
+}
+
+fn is_symbol_continue(c: char) -> bool {
+    c == '_' || is_xid_continue(c)
+}
+
+impl Tokenizer<'_> {
+    fn peek_char(&self) -> Option<char> {
+        let b = *self.source.as_bytes().get(self.pos)?;
+        if b.is_ascii() {
+            Some(b as char)
+        } else {
+            self.source[self.pos..].chars().next()
         }
-        if let Some(c) = self.chars.peek() {
-            if c.is_alphanumeric() {
-                return Err("Number cannot be followed by a letter".to_string());
+    }
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+    fn bump_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while self.peek_char().is_some_and(&predicate) {
+            self.bump();
+        }
+    }
+    fn next_number(&mut self) -> Token {
+        self.bump_while(|c| c.is_ascii_digit());
+        if self.peek_char().is_some_and(is_symbol_continue) {
+            self.bump_while(is_symbol_continue);
+            return Token::Error(LexError::NumberFollowedByLetter);
+        }
+        Token::Number
+    }
+    fn next_operator(&mut self, c: char) -> Token {
+        self.bump();
+        Token::Operator(c)
+    }
+    fn next_symbol(&mut self, start: usize) -> Token {
+        self.bump_while(is_symbol_continue);
+        let name = &self.source[start..self.pos];
+        if let Some(keyword) = Keyword::lookup(name) {
+            return Token::Keyword(keyword);
+        }
+        // Identifiers are compared in Normalization Form C so that the same
+        // name typed with precomposed or combining characters is one name.
+        let symbol = match is_nfc_quick(name.chars()) {
+            IsNormalized::Yes => self.interner.intern(name),
+            _ => {
+                let name: String = name.nfc().collect();
+                match Keyword::lookup(&name) {
+                    Some(keyword) => return Token::Keyword(keyword),
+                    None => self.interner.intern(&name),
+                }
+            },
+        };
+        Token::Symbol(symbol)
+    }
+    /// A string runs to the next `"` on the same line; it has no escapes.
+    fn next_string(&mut self) -> Token {
+        self.bump();
+        self.bump_while(|c| c != '"' && c != '\n');
+        match self.bump_if('"') {
+            true => Token::String,
+            false => Token::Error(LexError::UnterminatedString),
+        }
+    }
+    fn bump_if(&mut self, expected: char) -> bool {
+        let matches = self.peek_char() == Some(expected);
+        if matches {
+            self.bump();
+        }
+        matches
+    }
+    fn next_invalid(&mut self) -> Token {
+        let c = self.bump().unwrap_or_default();
+        match CONFUSABLES.iter().position(|(confusable, ..)| *confusable == c) {
+            Some(i) => Token::Error(LexError::Confusable(i)),
+            None => Token::Error(LexError::InvalidCharacter(c)),
+        }
+    }
+    /// Skips whitespace and comments, which run from `//` to the end of the
+    /// line.
+    fn skip_trivia(&mut self) {
+        loop {
+            self.bump_while(char::is_whitespace);
+            if !self.source[self.pos..].starts_with("//") {
+                return;
             }
-        } 
-        Ok(Token::Number(str))
+            let start = self.pos;
+            self.bump_while(|c| c != '\n');
+            self.comments.push(Span::new(start, self.pos));
+        }
     }
-    fn next_operator(&mut self) -> Result<Token, String> {
-        let c = self.chars.next().ok_or("Expected operator but found end of input")?;
-        Ok(Token::Operator(c))
+    /// Next token of the input, including erroneous ones.
+    pub fn next_raw(&mut self) -> Option<(Token, Span)> {
+        self.skip_trivia();
+        let start = self.pos;
+        let token = match self.peek_char() {
+            Some(c) if c.is_ascii_digit() => self.next_number(),
+            Some(c) if is_symbol_start(c) => self.next_symbol(start),
+            Some('"') => self.next_string(),
+            Some(c) if c.is_ascii_punctuation() => self.next_operator(c),
+            Some(_) => self.next_invalid(),
+            None => return None,
+        };
+        Some((token, Span::new(start, self.pos)))
     }
-    fn next_symbol(&mut self) -> Result<Token, String> {
-        let mut str = String::new();
-        while let Some(c) = self.chars.peek() {
-            if !c.is_alphanumeric() { break; }
-            str.push(*c);
-            self.chars.next();
+    /// Next token of the input, recording and skipping over the erroneous
+    /// ones so that lexing can go on after an invalid character.
+    fn next_valid(&mut self) -> Option<(Token, Span)> {
+        loop {
+            match self.next_raw()? {
+                (Token::Error(e), span) => self.errors.push((e, span)),
+                next => return Some(next),
+            }
         }
-        Ok(Token::Symbol(str))
     }
 }
 
-impl Iterator for Tokenizer {
-    type Item = Result<Token, String>;
+impl Iterator for Tokenizer<'_> {
+    type Item = Token;
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next()
+        let (token, span) = match self.peeked.take() {
+            Some(next) => next,
+            None => self.next_valid(),
+        }?;
+        self.span = span;
+        Some(token)
     }
 }
 
-impl Tokenizer {
-    pub fn peek(&mut self) -> Option<&Result<Token, String>> {
-        self.inner.peek()
+impl Tokenizer<'_> {
+    /// Error for the keyword last returned by `next`, found where an
+    /// identifier was expected.
+    pub fn reserved_keyword_error(&self, keyword: Keyword) -> CompileError {
+        CompileError::parse(format!("Expected symbol but found keyword '{}'", keyword), self.span)
+            .with_code(ErrorCode::ReservedKeyword)
+            .with_note(format!("'{}' is a reserved keyword and cannot be used as an identifier", keyword))
+            .with_help(format!("use another name, such as '{}_'", keyword))
+    }
+
+    /// Error for what `next` returned when something else was expected.
+    pub fn unexpected(&self, token: Option<Token>, expected: &str) -> CompileError {
+        let error = match token {
+            Some(Token::Error(e)) => return e.into_error(self.span),
+            Some(Token::Keyword(k)) if expected == "symbol" => return self.reserved_keyword_error(k),
+            Some(Token::Number) => CompileError::parse(format!("Expected {} but found number: {}", expected, self.text()), self.span),
+            Some(Token::Operator(c)) => CompileError::parse(format!("Expected {} but found operator: {}", expected, c), self.span),
+            Some(Token::Symbol(_)) => CompileError::parse(format!("Expected {} but found symbol: {}", expected, self.text()), self.span),
+            Some(Token::Keyword(k)) => CompileError::parse(format!("Expected {} but found keyword: {}", expected, k), self.span),
+            Some(Token::String) => CompileError::parse(format!("Expected {} but found string: {}", expected, self.text()), self.span),
+            None => CompileError::parse(format!("Expected {} but found end of input", expected), self.end_span()),
+        };
+        error.with_code(ErrorCode::UnexpectedToken)
+    }
+
+    /// Like `unexpected`, for the token `peek` returns, which is left to
+    /// parse.
+    pub fn unexpected_peeked(&mut self, expected: &str) -> CompileError {
+        let token = self.peek().copied();
+        let peeked = self.peek_span();
+        let span = std::mem::replace(&mut self.span, peeked);
+        let error = self.unexpected(token, expected);
+        self.span = span;
+        error
+    }
+
+    /// Error for the token last returned by `next`, with a custom message.
+    fn unexpected_text(&self, message: String) -> CompileError {
+        CompileError::parse(message, self.span).with_code(ErrorCode::UnexpectedToken)
+    }
+
+    /// Empty span at the end of the input.
+    pub fn end_span(&self) -> Span {
+        Span::new(self.source.len(), self.source.len())
+    }
+
+    pub fn peek(&mut self) -> Option<&Token> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.next_valid());
+        }
+        self.peeked.as_ref().and_then(|next| next.as_ref().map(|(token, _)| token))
+    }
+
+    /// Span of the token last returned by `next`.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Span of the token which `peek` returns, or an empty span at the end
+    /// of the input.
+    pub fn peek_span(&mut self) -> Span {
+        self.peek();
+        match &self.peeked {
+            Some(Some((_, span))) => *span,
+            _ => self.end_span(),
+        }
+    }
+
+    /// Source text of the token last returned by `next`.
+    pub fn text(&self) -> &str {
+        self.slice(self.span)
+    }
+
+    pub fn slice(&self, span: Span) -> &str {
+        &self.source[span.start..span.end]
+    }
+
+    /// Name of an interned symbol.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        self.interner.resolve(symbol)
+    }
+
+    /// Spans of the comments met so far, in order. Comments are not tokens,
+    /// but the formatter keeps them.
+    pub fn comments(&self) -> &[Span] {
+        &self.comments
+    }
+
+    /// Lexical errors met so far, removing them from the tokenizer.
+    /// Whether an invalid token was skipped within `span`.
+    pub fn skipped_error_in(&self, span: Span) -> bool {
+        self.errors.iter().any(|(_, error)| span.start <= error.start && error.end <= span.end)
+    }
+
+    pub fn take_errors(&mut self) -> Vec<(LexError, Span)> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// One-based line and column of a byte offset in the input. Columns
+    /// count characters, not bytes.
+    pub fn location(&self, offset: usize) -> (usize, usize) {
+        let before = &self.source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.bytes().filter(|&b| b == b'\n').count() + 1;
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    pub fn expect_symbol(&mut self) -> Result<String, CompileError> {
+        match self.next() {
+            Some(Token::Symbol(s)) => Ok(self.resolve(s).to_string()),
+            token => Err(self.unexpected(token, "symbol")),
+        }
     }
 
-    pub fn expect_symbol(&mut self) -> Result<String, String> {
+    /// The contents of a string, without its quotes.
+    pub fn expect_string(&mut self) -> Result<String, CompileError> {
         match self.next() {
-            Some(Ok(Token::Symbol(s))) => Ok(s),
-            Some(Ok(Token::Number(n))) => Err(format!("Expected symbol but found number: {}", n)),
-            Some(Ok(Token::Operator(c))) => Err(format!("Expected symbol but found operator: {}", c)),
-            Some(Err(e)) => Err(e),
-            None => Err("Expected symbol but found end of input".to_string()),
+            Some(Token::String) => Ok(self.text()[1..self.text().len() - 1].to_string()),
+            token => Err(self.unexpected(token, "string")),
         }
     }
 
-    pub fn expect_operator(&mut self) -> Result<char, String> {
+    pub fn expect_operator(&mut self) -> Result<char, CompileError> {
         match self.next() {
-            Some(Ok(Token::Operator(c))) => Ok(c),
-            Some(Ok(Token::Number(n))) => Err(format!("Expected operator but found number: {}", n)),
-            Some(Ok(Token::Symbol(s))) => Err(format!("Expected operator but found symbol: {}", s)),
-            Some(Err(e)) => Err(e),
-            None => Err("Expected operator but found end of input".to_string()),
+            Some(Token::Operator(c)) => Ok(c),
+            token => Err(self.unexpected(token, "operator")),
         }
     }
 
-    pub fn expect_symbol_of(&mut self, expected: &str) -> Result<(), String> {
+    pub fn expect_symbol_of(&mut self, expected: &str) -> Result<(), CompileError> {
         match self.expect_symbol() {
             Ok(s) if s == expected => Ok(()),
-            Ok(s) => Err(format!("Expected symbol '{}' but found '{}'", expected, s)),
+            Ok(s) => Err(self.unexpected_text(format!("Expected symbol '{}' but found '{}'", expected, s))),
             Err(e) => Err(e),
         }
     }
 
-    pub fn expect_operator_of(&mut self, expected: char) -> Result<(), String> {
-        match self.expect_operator() {
-            Ok(c) if c == expected => Ok(()),
-            Ok(c) => Err(format!("Expected operator '{}' but found '{}'", expected, c)),
-            Err(e) => Err(e),
+    pub fn expect_keyword(&mut self, expected: Keyword) -> Result<(), CompileError> {
+        match self.next() {
+            Some(Token::Keyword(k)) if k == expected => Ok(()),
+            token => Err(self.unexpected(token, &format!("keyword '{}'", expected))),
         }
     }
-}
 
-impl Iterator for TokenizerInner {
-    type Item = Result<Token, String>;
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.chars.peek() {
-            Some(c) if c.is_whitespace() => {self.chars.next(); self.next()},
-            Some(c) if c.is_numeric() => Some(self.next_number()),
-            Some(c) if c.is_ascii_punctuation() => Some(self.next_operator()),
-            Some(c) if c.is_alphanumeric() => Some(self.next_symbol()),
-            Some(_) => None,
-            None => None,
+    pub fn expect_operator_of(&mut self, expected: char) -> Result<(), CompileError> {
+        match self.expect_operator() {
+            Ok(c) if c == expected => Ok(()),
+            Ok(c) => Err(self.unexpected_text(format!("Expected operator '{}' but found '{}'", expected, c))),
+            Err(e) => Err(e),
         }
     }
 }
@@ -159,63 +546,256 @@ impl Iterator for TokenizerInner {
 mod tests {
     use super::*;
 
+    /// A token together with its text, to compare against expected tokens.
+    #[derive(Debug, PartialEq)]
+    enum T<'s> {
+        Number(&'s str),
+        Operator(char),
+        Symbol(&'s str),
+        Keyword(Keyword),
+        String(&'s str),
+        Error(LexError),
+    }
+
+    fn describe<'s>(tokenizer: &'s Tokenizer, token: Token, span: Span) -> T<'s> {
+        match token {
+            Token::Number => T::Number(tokenizer.slice(span)),
+            Token::Operator(c) => T::Operator(c),
+            Token::Symbol(s) => T::Symbol(tokenizer.resolve(s)),
+            Token::Keyword(k) => T::Keyword(k),
+            Token::String => T::String(tokenizer.slice(span)),
+            Token::Error(e) => T::Error(e),
+        }
+    }
+
+    /// Next token including the erroneous ones.
+    fn next<'s>(tokenizer: &'s mut Tokenizer) -> Option<T<'s>> {
+        let (token, span) = tokenizer.next_raw()?;
+        Some(describe(tokenizer, token, span))
+    }
+
+    /// Next token as seen by the parser.
+    fn next_valid<'s>(tokenizer: &'s mut Tokenizer) -> Option<T<'s>> {
+        let token = tokenizer.next()?;
+        Some(describe(tokenizer, token, tokenizer.span()))
+    }
+
     #[test]
     fn test_tokenizer_mixed_input() {
         let data = "3+5 *2-8 /4";
-        let mut tokenizer = TokenizerInner::try_from(data).unwrap();
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Number("3".to_string()))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Operator('+'))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Number("5".to_string()))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Operator('*'))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Number("2".to_string()))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Operator('-'))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Number("8".to_string()))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Operator('/'))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Number("4".to_string()))));
-        assert_eq!(tokenizer.next(), None);
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Number("3")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('+')));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("5")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('*')));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("2")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('-')));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("8")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('/')));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("4")));
+        assert_eq!(next(&mut tokenizer), None);
     }
 
     #[test]
     fn test_tokenizer_empty_input() {
         let data = "";
-        let mut tokenizer = TokenizerInner::try_from(data).unwrap();
-        assert_eq!(tokenizer.next(), None);
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_strings() {
+        let mut tokenizer = Tokenizer::from("test \"a \\ b\" \"unterminated\n\"\"");
+        assert_eq!(next(&mut tokenizer), Some(T::Keyword(Keyword::Test)));
+        assert_eq!(next(&mut tokenizer), Some(T::String("\"a \\ b\"")));
+        assert_eq!(next(&mut tokenizer), Some(T::Error(LexError::UnterminatedString)));
+        assert_eq!(next(&mut tokenizer), Some(T::String("\"\"")));
+        assert_eq!(next(&mut tokenizer), None);
     }
 
     #[test]
     fn test_tokenizer_variable() {
         let data = "abc";
-        let mut tokenizer = TokenizerInner::try_from(data).unwrap();
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Symbol("abc".to_string()))));
-        assert_eq!(tokenizer.next(), None);
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("abc")));
+        assert_eq!(next(&mut tokenizer), None);
     }
 
     #[test]
     fn test_tokenizer_number() {
         let data = "42";
-        let mut tokenizer = TokenizerInner::try_from(data).unwrap();
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Number("42".to_string()))));
-        assert_eq!(tokenizer.next(), None);
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Number("42")));
+        assert_eq!(next(&mut tokenizer), None);
     }
 
     #[test]
     fn test_tokenizer_mixed_symbols() {
         let data = "var1 + var2";
-        let mut tokenizer = TokenizerInner::try_from(data).unwrap();
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Symbol("var1".to_string()))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Operator('+'))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Symbol("var2".to_string()))));
-        assert_eq!(tokenizer.next(), None);
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("var1")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('+')));
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("var2")));
+        assert_eq!(next(&mut tokenizer), None);
     }
 
     #[test]
     fn test_tokenizer_symbols_with_numbers() {
         let data = "var123 + 456var";
-        let mut tokenizer = TokenizerInner::try_from(data).unwrap();
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Symbol("var123".to_string()))));
-        assert_eq!(tokenizer.next(), Some(Ok(Token::Operator('+'))));
-        let x = tokenizer.next();
-        assert!(x.is_some());
-        assert!(x.unwrap().is_err());
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("var123")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('+')));
+        let x = next(&mut tokenizer);
+        assert!(matches!(x, Some(T::Error(LexError::NumberFollowedByLetter))));
+    }
+
+    #[test]
+    fn test_tokenizer_keywords() {
+        let data = "let x return lets";
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Keyword(Keyword::Let)));
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("x")));
+        assert_eq!(next(&mut tokenizer), Some(T::Keyword(Keyword::Return)));
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("lets")));
+        assert_eq!(next(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_reserved_keywords() {
+        for keyword in Keyword::ALL {
+            let mut tokenizer = Tokenizer::from(keyword.as_str());
+            assert_eq!(next(&mut tokenizer), Some(T::Keyword(keyword)));
+        }
+    }
+
+    #[test]
+    fn test_tokenizer_underscores() {
+        let data = "my_var + _x1";
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("my_var")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('+')));
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("_x1")));
+        assert_eq!(next(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_unicode_symbol() {
+        let data = "π * grüße";
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("π")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('*')));
+        assert_eq!(next(&mut tokenizer), Some(T::Symbol("grüße")));
+        assert_eq!(next(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_nfc_normalization() {
+        // "é" precomposed and as "e" followed by a combining acute accent
+        let mut composed = Tokenizer::from("caf\u{e9}");
+        let mut decomposed = Tokenizer::from("cafe\u{301}");
+        assert_eq!(next(&mut composed), next(&mut decomposed));
+    }
+
+    #[test]
+    fn test_tokenizer_confusable() {
+        let data = "1 \u{2212} 2";
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Number("1")));
+        match next(&mut tokenizer) {
+            Some(T::Error(e)) => assert!(e.to_string().contains("looks like '-'"), "unexpected error: {}", e),
+            x => panic!("Expected confusable error ; got {:?}", x),
+        }
+        assert_eq!(next(&mut tokenizer), Some(T::Number("2")));
+    }
+
+    #[test]
+    fn test_tokenizer_invalid_character() {
+        let data = "1 § 2";
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Number("1")));
+        match next(&mut tokenizer) {
+            Some(T::Error(e)) => assert!(e.to_string().contains("U+00A7"), "unexpected error: {}", e),
+            x => panic!("Expected invalid character error ; got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_tokenizer_owned_string() {
+        let data = format!("{} + {}", "a", 1);
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Symbol("a")));
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Operator('+')));
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Number("1")));
+        assert_eq!(next_valid(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_borrowed_string() {
+        let data = String::from("x");
+        let mut tokenizer = Tokenizer::from(data.as_str());
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Symbol("x")));
+        assert_eq!(next_valid(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_reader() {
+        let reader = std::io::Cursor::new("let y");
+        let mut tokenizer = Tokenizer::from_reader(reader).unwrap();
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Keyword(Keyword::Let)));
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Symbol("y")));
+        assert_eq!(next_valid(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_spans() {
+        let data = "ab + 12\n  é";
+        let mut tokenizer = Tokenizer::from(data);
+        let spans = std::iter::from_fn(|| tokenizer.next_raw()).map(|(_, span)| span).collect::<Vec<_>>();
+        assert_eq!(spans, vec![Span::new(0, 2), Span::new(3, 4), Span::new(5, 7), Span::new(10, 12)]);
+    }
+
+    #[test]
+    fn test_tokenizer_error_recovery() {
+        let data = "1 § 2 🙂 3";
+        let mut tokenizer = Tokenizer::from(data);
+        assert_eq!(next(&mut tokenizer), Some(T::Number("1")));
+        assert!(matches!(tokenizer.next_raw(), Some((Token::Error(_), Span { start: 2, end: 4 }))));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("2")));
+        assert!(matches!(tokenizer.next_raw(), Some((Token::Error(_), Span { start: 7, end: 11 }))));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("3")));
+        assert_eq!(next(&mut tokenizer), None);
+    }
+
+    #[test]
+    fn test_tokenizer_collects_errors() {
+        let mut tokenizer = Tokenizer::from("§ 1 +\n 🙂 2");
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Number("1")));
+        assert_eq!(tokenizer.span(), Span::new(3, 4));
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Operator('+')));
+        assert_eq!(next_valid(&mut tokenizer), Some(T::Number("2")));
+        assert_eq!(next_valid(&mut tokenizer), None);
+        let errors = tokenizer.take_errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(tokenizer.location(errors[0].1.start), (1, 1));
+        assert_eq!(tokenizer.location(errors[1].1.start), (2, 2));
+    }
+
+    #[test]
+    fn test_tokenizer_comments() {
+        let mut tokenizer = Tokenizer::from("// one\n1 / 2 // two\r\n//\n3");
+        assert_eq!(next(&mut tokenizer), Some(T::Number("1")));
+        assert_eq!(next(&mut tokenizer), Some(T::Operator('/')));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("2")));
+        assert_eq!(next(&mut tokenizer), Some(T::Number("3")));
+        assert_eq!(next(&mut tokenizer), None);
+        assert_eq!(tokenizer.comments(), [Span::new(0, 6), Span::new(13, 20), Span::new(21, 23)]);
+    }
+
+    #[test]
+    fn test_tokenizer_interning() {
+        let tokenizer = Tokenizer::from("abc + abc + abd");
+        let tokens = tokenizer.collect::<Vec<_>>();
+        assert_eq!(tokens[0], tokens[2]);
+        assert_ne!(tokens[0], tokens[4]);
     }
 }
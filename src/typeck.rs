@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use crate::block::{Block, Line};
+use crate::code::ErrorCode;
+use crate::error::{Applicability, CompileError};
+use crate::expression::Expression;
+use crate::parser::Ast;
+use crate::span::Span;
+use crate::suggest;
+
+/// The type of a value. Number literals are `f64`; `i64` values are made
+/// with `as i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    F64,
+    I64,
+}
+
+impl Type {
+    pub const ALL: [Type; 2] = [Type::F64, Type::I64];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Type::F64 => "f64",
+            Type::I64 => "i64",
+        }
+    }
+
+    pub fn lookup(name: &str) -> Option<Type> {
+        Type::ALL.into_iter().find(|type_| type_.as_str() == name)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Checks that declared types exist and that the operands of arithmetic
+/// and the values of `let` statements have the expected types.
+pub fn check(program: &[Ast]) -> Vec<CompileError> {
+    check_program(program).errors
+}
+
+/// The type of each `let` binding of a program whose type is known, even
+/// if the program has errors, by the span of its name.
+pub fn bindings(program: &[Ast]) -> Vec<(Span, Type)> {
+    check_program(program).bindings
+}
+
+fn check_program(program: &[Ast]) -> Checker {
+    let mut checker = Checker { scopes: vec![HashMap::new()], errors: Vec::new(), bindings: Vec::new() };
+    for ast in program {
+        match ast {
+            Ast::Expression(expression) => {
+                checker.expression(expression);
+            },
+            Ast::Test(test) => {
+                checker.block(&test.body);
+            },
+        }
+    }
+    checker
+}
+
+/// Checks an entry of the REPL with the types of the variables of the
+/// previous entries, adding those of its own `let` statements. Returns the
+/// type of its value.
+pub fn check_entry(block: &Block, variables: &mut HashMap<String, Type>) -> Result<Option<Type>, Vec<CompileError>> {
+    let mut checker = Checker { scopes: vec![variables.clone()], errors: Vec::new(), bindings: Vec::new() };
+    let type_ = checker.lines(block);
+    if !checker.errors.is_empty() {
+        return Err(checker.errors);
+    }
+    *variables = checker.scopes.pop().expect("the scope of the entry is left");
+    Ok(type_)
+}
+
+struct Checker {
+    /// Types of the variables of each block being checked, innermost last.
+    scopes: Vec<HashMap<String, Type>>,
+    errors: Vec<CompileError>,
+    bindings: Vec<(Span, Type)>,
+}
+
+impl Checker {
+    /// The type of a block is the one of its first `return`, as the lines
+    /// after it are never run.
+    fn block(&mut self, block: &Block) -> Option<Type> {
+        self.scopes.push(HashMap::new());
+        let type_ = self.lines(block);
+        self.scopes.pop();
+        type_
+    }
+
+    fn lines(&mut self, block: &Block) -> Option<Type> {
+        let mut returned = None;
+        for (i, line) in block.lines.iter().enumerate() {
+            match line {
+                Line::Expression(expression) => {
+                    self.expression(expression);
+                },
+                Line::LetStatement { name, span: name_span, value, type_ } => {
+                    let found = self.expression(value);
+                    let declared = type_.as_ref().and_then(|(type_, span)| self.type_(type_, *span));
+                    if let (Some(declared), Some(found), Some((_, span))) = (declared, found, type_) {
+                        if declared != found {
+                            let line = block.info.get(i).map_or(*span, |info| info.span);
+                            self.errors.push(
+                                CompileError::type_(format!("Expected {} but found {}", declared, found), line)
+                                    .with_code(ErrorCode::MismatchedTypes)
+                                    .with_label(*span, "expected because of this type")
+                                    .with_help(format!("convert the value with 'as {}'", declared)),
+                            );
+                        }
+                    }
+                    // Declaring the type of a variable sets it even when the
+                    // value is wrong, so that uses don't report more errors.
+                    if let Some(type_) = declared.or(found) {
+                        self.scopes.last_mut().expect("a block has a scope").insert(name.clone(), type_);
+                        self.bindings.push((*name_span, type_));
+                    }
+                },
+                Line::ReturnStatement(expression) => {
+                    let type_ = self.expression(expression);
+                    returned = returned.or(Some(type_));
+                },
+                Line::Assert { left, comparison, right, span } => {
+                    if let (Some(x), Some(y)) = (self.expression(left), self.expression(right)) {
+                        if x != y {
+                            self.errors.push(
+                                CompileError::type_(format!("Cannot compare {} and {}", x, y), *span)
+                                    .with_code(ErrorCode::MismatchedTypes)
+                                    .with_note(format!("both sides of '{}' must have the same type", comparison.as_str())),
+                            );
+                        }
+                    }
+                },
+                Line::Error(_) => (),
+            }
+        }
+        returned.flatten()
+    }
+
+    /// The type of an expression, or `None` if it is unknown because of an
+    /// error reported elsewhere.
+    fn expression(&mut self, expression: &Expression) -> Option<Type> {
+        match expression {
+            Expression::Literal(..) => Some(Type::F64),
+            Expression::Variable(name, _) => self.scopes.iter().rev().find_map(|scope| scope.get(name).copied()),
+            Expression::Block(block) => self.block(block),
+            Expression::Add(a, b, span) => self.binary('+', a, b, *span),
+            Expression::Sub(a, b, span) => self.binary('-', a, b, *span),
+            Expression::Mul(a, b, span) => self.binary('*', a, b, *span),
+            Expression::Div(a, b, span) => self.binary('/', a, b, *span),
+            Expression::Cast(value, type_, span) => {
+                self.expression(value);
+                self.type_(type_, *span)
+            },
+        }
+    }
+
+    fn binary(&mut self, operator: char, a: &Expression, b: &Expression, span: Span) -> Option<Type> {
+        let (a, b) = (self.expression(a), self.expression(b));
+        match (a, b) {
+            (Some(a), Some(b)) if a != b => {
+                self.errors.push(
+                    CompileError::type_(format!("Cannot apply '{}' to {} and {}", operator, a, b), span)
+                        .with_code(ErrorCode::MismatchedTypes)
+                        .with_help(format!("convert one of the operands with 'as {}' or 'as {}'", a, b)),
+                );
+                Some(a)
+            },
+            _ => a.or(b),
+        }
+    }
+
+    /// The type named `name`, reporting an error at `span` if there is none.
+    fn type_(&mut self, name: &str, span: Span) -> Option<Type> {
+        // The span of a cast includes `as`.
+        let name_span = Span::new(span.end - name.len(), span.end);
+        let type_ = Type::lookup(name);
+        if type_.is_none() {
+            let error = CompileError::type_(format!("Cannot find type '{}'", name), name_span)
+                .with_code(ErrorCode::UnknownType)
+                .with_note(format!("the types are {}", Type::ALL.map(|type_| type_.as_str()).join(" and ")));
+            self.errors.push(match suggest::closest(name, Type::ALL.iter().map(Type::as_str)) {
+                Some(similar) => {
+                    error.with_suggestion(name_span, similar, "a type with a similar name exists", Applicability::MaybeIncorrect)
+                },
+                None => error,
+            });
+        }
+        type_
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn check_str(data: &str) -> Vec<CompileError> {
+        let program = Parser::from(data).collect::<Result<Vec<_>, _>>().expect("test programs are valid");
+        check(&program)
+    }
+
+    #[test]
+    fn test_well_typed() {
+        assert!(check_str("{ let x: i64 = 7 as i64; let y := x / (2 as i64); (x + y) as f64 * 2 }").is_empty());
+        assert!(check_str("{ let x := { return 1 as i64; 2 }; x * x } as f64").is_empty());
+    }
+
+    #[test]
+    fn test_bindings() {
+        let program = Parser::from("{ let x := 1 as i64; let y: f64 = x; y }").collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(bindings(&program), [(Span::new(6, 7), Type::I64), (Span::new(25, 26), Type::F64)]);
+    }
+
+    #[test]
+    fn test_mismatched_types() {
+        let errors = check_str("{ let x := 1 as i64; x + 2 }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Cannot apply '+' to i64 and f64");
+        assert_eq!(errors[0].span, Some(Span::new(23, 24)));
+        let errors = check_str("{ let x: i64 = 1;\n x }");
+        assert_eq!(errors[0].message, "Expected i64 but found f64");
+        assert_eq!(errors[0].span, Some(Span::new(2, 16)));
+        assert_eq!(errors[0].labels[0].span, Span::new(9, 12));
+    }
+
+    #[test]
+    fn test_unknown_types() {
+        let errors = check_str("{ let x: f46 = 1; x as u8 }");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "Cannot find type 'f46'");
+        assert_eq!(errors[0].span, Some(Span::new(9, 12)));
+        assert_eq!(errors[0].suggestions[0].replacement, "f64");
+        assert_eq!(errors[1].span, Some(Span::new(23, 25)));
+        assert!(errors[1].suggestions.is_empty());
+    }
+}
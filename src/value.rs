@@ -0,0 +1,118 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::typeck::Type;
+
+/// The bound of the `f64` values which convert to `i64`, excluded: 2^63.
+const I64_LIMIT: f64 = 9223372036854775808.;
+
+/// An operation which fails the runtime checks of the generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    DivisionByZero,
+    /// `i64::MIN / -1`.
+    DivisionOverflow,
+    /// An `f64` converted to `i64` which is out of its range, or NaN.
+    OutOfRange,
+}
+
+impl Display for Fault {
+    /// The message of the runtime error.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Fault::DivisionByZero => write!(f, "division by zero"),
+            Fault::DivisionOverflow => write!(f, "attempt to divide with overflow"),
+            Fault::OutOfRange => write!(f, "out-of-range conversion to i64"),
+        }
+    }
+}
+
+/// A value computed by a program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    F64(f64),
+    I64(i64),
+}
+
+impl Value {
+    pub fn type_(&self) -> Type {
+        match self {
+            Value::F64(_) => Type::F64,
+            Value::I64(_) => Type::I64,
+        }
+    }
+
+    /// The result of an arithmetic operation on values of the same type,
+    /// with the semantics of the generated code: `i64` arithmetic wraps,
+    /// and faults are what its runtime checks stop.
+    pub fn binary(operator: char, x: Value, y: Value) -> Result<Value, Fault> {
+        Ok(match (x, y) {
+            (Value::I64(_), Value::I64(0)) if operator == '/' => return Err(Fault::DivisionByZero),
+            (Value::I64(i64::MIN), Value::I64(-1)) if operator == '/' => return Err(Fault::DivisionOverflow),
+            (Value::I64(x), Value::I64(y)) => Value::I64(match operator {
+                '+' => x.wrapping_add(y),
+                '-' => x.wrapping_sub(y),
+                '*' => x.wrapping_mul(y),
+                _ => x / y,
+            }),
+            (Value::F64(x), Value::F64(y)) => Value::F64(match operator {
+                '+' => x + y,
+                '-' => x - y,
+                '*' => x * y,
+                _ => x / y,
+            }),
+            (x, y) => unreachable!("'{}' applied to {} and {}", operator, x.type_(), y.type_()),
+        })
+    }
+
+    /// A value converted with `as`.
+    pub fn convert(self, type_: Type) -> Result<Value, Fault> {
+        Ok(match (self, type_) {
+            // NaN fails both comparisons, so it is out of range as well.
+            (Value::F64(value), Type::I64) if !(-I64_LIMIT..I64_LIMIT).contains(&value) => return Err(Fault::OutOfRange),
+            (Value::F64(value), Type::I64) => Value::I64(value as i64),
+            (Value::I64(value), Type::F64) => Value::F64(value as f64),
+            (value, _) => value,
+        })
+    }
+
+    /// The value of a type stored in 8 bytes.
+    pub fn from_bits(type_: Type, bits: u64) -> Self {
+        match type_ {
+            Type::F64 => Value::F64(f64::from_bits(bits)),
+            Type::I64 => Value::I64(bits as i64),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Value::F64(value) => write!(f, "{}", value),
+            Value::I64(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary() {
+        assert_eq!(Value::binary('-', Value::I64(i64::MIN), Value::I64(1)), Ok(Value::I64(i64::MAX)));
+        assert_eq!(Value::binary('/', Value::I64(-7), Value::I64(2)), Ok(Value::I64(-3)));
+        assert_eq!(Value::binary('/', Value::I64(1), Value::I64(0)), Err(Fault::DivisionByZero));
+        assert_eq!(Value::binary('/', Value::I64(i64::MIN), Value::I64(-1)), Err(Fault::DivisionOverflow));
+        assert_eq!(Value::binary('/', Value::F64(1.), Value::F64(0.)), Ok(Value::F64(f64::INFINITY)));
+    }
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(Value::F64(-2.9).convert(Type::I64), Ok(Value::I64(-2)));
+        assert_eq!(Value::F64(-I64_LIMIT).convert(Type::I64), Ok(Value::I64(i64::MIN)));
+        for value in [I64_LIMIT, f64::NAN, f64::NEG_INFINITY] {
+            assert_eq!(Value::F64(value).convert(Type::I64), Err(Fault::OutOfRange), "{}", value);
+        }
+        assert_eq!(Value::I64(3).convert(Type::F64), Ok(Value::F64(3.)));
+    }
+}
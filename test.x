@@ -1,3 +0,0 @@
-30 + 12
-
-+ 6
\ No newline at end of file
@@ -0,0 +1,61 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+/// A directory of programs, made anew.
+fn programs(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = common::temp_path(name);
+    let _ = fs::remove_dir_all(&dir);
+    for (path, code) in files {
+        common::write(&format!("{}/{}", name, path), code);
+    }
+    dir
+}
+
+fn check(args: &[&str], paths: &[PathBuf]) -> Output {
+    common::output(common::lang().arg("check").args(args).args(paths))
+}
+
+#[test]
+fn test_check_files_and_directories() {
+    let dir = programs(
+        "check_programs",
+        &[
+            ("ok.x", "1 + 2"),
+            ("notes.txt", "not a program"),
+            ("nested/unused.x", "{ let x := 1; 2 }"),
+            ("nested/deeper/broken.x", "{ let y := 1; y + z }"),
+        ],
+    );
+    let output = check(&[], &[dir.join("ok.x")]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty() && output.stderr.is_empty());
+
+    let output = check(&[], std::slice::from_ref(&dir));
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let broken = stderr.find("deeper/broken.x:1:19").expect(&stderr);
+    let unused = stderr.find("unused.x:1:7").expect(&stderr);
+    assert!(broken < unused, "directories are checked in order: {}", stderr);
+    assert!(stderr.contains("Cannot find variable 'z' in this scope"), "{}", stderr);
+
+    // Only denied lints fail.
+    let output = check(&["-D", "unused_variables"], &[dir.join("nested/unused.x")]);
+    assert_eq!(output.status.code(), Some(1));
+    let output = check(&[], &[dir.join("nested/unused.x")]);
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn test_check_json() {
+    let dir = programs("check_json", &[("a.x", "1 +"), ("b.x", "{ let x := 1 as i64; x + 1 }")]);
+    let output = check(&["--error-format", "json"], &[dir.join("a.x"), dir.join("b.x"), dir.join("missing.x")]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines = stderr.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "{}", stderr);
+    assert!(lines[0].contains("a.x") && lines[1].contains("b.x") && lines[2].contains("missing.x"), "{}", stderr);
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the integration tests, which run the compiler binary.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// The compiler, to give arguments to.
+pub fn lang() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_lang"))
+}
+
+/// Runs the compiler and waits for its output.
+pub fn output(command: &mut Command) -> Output {
+    command.output().expect("failed to run the compiler")
+}
+
+/// Runs the compiler with `stdin` as its input and waits for its output.
+pub fn output_with_stdin(command: &mut Command, stdin: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run the compiler");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// A path in the temporary directory of the tests.
+pub fn temp_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// Writes a file in the temporary directory of the tests, creating its
+/// parent directories, and returns its path.
+pub fn write(name: &str, code: &str) -> PathBuf {
+    let path = temp_path(name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, code).unwrap();
+    path
+}
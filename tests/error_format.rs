@@ -0,0 +1,33 @@
+mod common;
+
+use serde_json::Value;
+
+fn diagnostics(code: &str, args: &[&str]) -> Vec<Value> {
+    let output = common::output(common::lang().args(["run", "--error-format=json", "-e", code]).args(args));
+    assert!(!output.status.success());
+    String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("invalid JSON line {:?}: {}", line, e)))
+        .collect()
+}
+
+#[test]
+fn test_one_object_per_diagnostic() {
+    let diagnostics = diagnostics("{ let x := 1; 2 +; 3 }\n+ (4 ；", &[]);
+    let messages = diagnostics.iter().map(|d| d["message"].as_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(messages.len(), 3, "unexpected diagnostics: {:?}", messages);
+    assert!(diagnostics.iter().all(|d| d["severity"] == "error" && d["file"] == "<eval>"));
+    assert_eq!(diagnostics[0]["spans"][0]["byte_start"], 17);
+    assert_eq!(diagnostics[0]["spans"][0]["line_start"], 1);
+    assert_eq!(diagnostics[1]["spans"][0]["line_start"], 2);
+    assert_eq!(diagnostics[1]["spans"][0]["column_start"], 6);
+    assert_eq!(diagnostics[1]["children"][0]["spans"][0]["suggested_replacement"], ";");
+}
+
+#[test]
+fn test_lint_levels() {
+    let diagnostics = diagnostics("{ let x := 1; 2 / 0 }", &["-D", "division_by_zero"]);
+    let severities = diagnostics.iter().map(|d| d["severity"].as_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(severities, ["error", "warning"]);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use std::process::Output;
+
+fn lang(args: &[&str]) -> Output {
+    common::output(common::lang().args(args))
+}
+
+#[test]
+fn test_explain() {
+    let output = lang(&["--explain", "E0011"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("A name does not refer to any variable in scope."), "{}", stdout);
+    assert!(stdout.contains("```compile_fail,E0011"));
+}
+
+#[test]
+fn test_explain_unknown_code() {
+    let output = lang(&["--explain", "E9999"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("unknown error code 'E9999'"));
+}
+
+#[test]
+fn test_errors_point_to_explanations() {
+    let output = lang(&["run", "-e", "{ let x = 1; let let := 2; x }"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("error[E0004]: Expected operator ':' but found '='"), "{}", stderr);
+    assert!(stderr.contains("Some errors have detailed explanations: E0004, E0005."), "{}", stderr);
+    assert!(stderr.contains("try `lang --explain E0004`"), "{}", stderr);
+}
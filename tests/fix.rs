@@ -0,0 +1,38 @@
+mod common;
+
+use std::fs;
+use std::process::Output;
+
+/// Runs `lang fix` on a file with the given content, returning its output
+/// and the content of the file afterwards.
+fn fix(name: &str, code: &str) -> (Output, String) {
+    let path = common::write(name, code);
+    let output = common::output(common::lang().arg("fix").arg(&path));
+    (output, fs::read_to_string(&path).unwrap())
+}
+
+#[test]
+fn test_fix_errors_and_warnings() {
+    let (output, fixed) = fix("fix_errors.x", "{\n  let x = 1\n  let unused := 2\n  x ； 3\n}");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fixed, "{\n  let x := 1;\n  let _unused := 2;\n  x ; 3\n}");
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("Fixed 5 issues in"));
+}
+
+#[test]
+fn test_keep_unsure_fixes() {
+    let code = "{ retrun 1; }";
+    let (output, fixed) = fix("fix_unsure.x", code);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(fixed, code);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("there is a keyword with a similar name: 'return'"));
+}
+
+#[test]
+fn test_nothing_to_fix() {
+    let code = "{ let x := 1; x }";
+    let (output, fixed) = fix("fix_nothing.x", code);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(fixed, code);
+}
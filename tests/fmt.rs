@@ -0,0 +1,42 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::write;
+
+fn fmt(args: &[&str], paths: &[&PathBuf]) -> Output {
+    common::output(common::lang().arg("fmt").args(args).args(paths))
+}
+
+#[test]
+fn test_format_in_place() {
+    let path = write("fmt_in_place.x", "{ let x:=1; // one\n x+2 }");
+    let output = fmt(&[], &[&path]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\n    let x := 1; // one\n    x + 2\n}\n");
+}
+
+#[test]
+fn test_check() {
+    let formatted = write("fmt_check_ok.x", "(1 - 2) - 3\n");
+    let unformatted = write("fmt_check_bad.x", "1 - (2 - 3)\n");
+    let output = fmt(&["--check"], &[&formatted]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    let output = fmt(&["--check"], &[&formatted, &unformatted]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, format!("Would reformat {}\n", unformatted.display()));
+    assert_eq!(fs::read_to_string(&unformatted).unwrap(), "1 - (2 - 3)\n");
+}
+
+#[test]
+fn test_syntax_errors() {
+    let path = write("fmt_errors.x", "{ 1 + }");
+    let output = fmt(&[], &[&path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("error[E0001]"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{ 1 + }");
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+
+use serde_json::{json, Value};
+
+/// A `lang lsp` process driven through JSON-RPC messages on its stdio.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = common::lang()
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run the language server");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client { child, stdin, stdout, next_id: 0 };
+        let result = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(result["capabilities"]["hoverProvider"], true);
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+            match header.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => length = value.parse().ok(),
+                Some(_) => (),
+                None => break,
+            }
+        }
+        let mut body = vec![0; length.expect("messages have a length")];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        let response = self.receive();
+        assert_eq!(response["id"], id, "{}", response);
+        response["result"].clone()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Opens or changes a document, returning the diagnostics published
+    /// for it.
+    fn open(&mut self, uri: &str, text: &str) -> Value {
+        let document = json!({ "uri": uri, "languageId": "lang", "version": 1, "text": text });
+        self.notify("textDocument/didOpen", json!({ "textDocument": document }));
+        let notification = self.receive();
+        assert_eq!(notification["method"], "textDocument/publishDiagnostics");
+        notification["params"]["diagnostics"].clone()
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+const URI: &str = "file:///tmp/test.x";
+
+fn at(line: u32, character: u32) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+#[test]
+fn test_diagnostics() {
+    let mut client = Client::start();
+    let diagnostics = client.open(URI, "{\n  let x := 1;\n  y\n}");
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0011");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"], json!({ "start": { "line": 2, "character": 2 }, "end": { "line": 2, "character": 3 } }));
+    assert!(diagnostics[0]["message"].as_str().unwrap().ends_with("help: a variable with a similar name exists: 'x'"));
+
+    // Changes replace the whole unsaved text.
+    let change = json!({
+        "textDocument": { "uri": URI, "version": 2 },
+        "contentChanges": [{ "text": "{ let x := 1; x }" }],
+    });
+    client.notify("textDocument/didChange", change);
+    let notification = client.receive();
+    assert_eq!(notification["params"]["diagnostics"], json!([]));
+    client.shutdown();
+}
+
+#[test]
+fn test_navigation() {
+    let mut client = Client::start();
+    client.open(URI, "{\n  let x := 1 as i64;\n  let y := { let z := x; z };\n  y\n}");
+
+    let hover = client.request("textDocument/hover", at(2, 22));
+    assert_eq!(hover["contents"]["value"], "```lang\nlet x: i64\n```");
+    assert_eq!(hover["range"]["start"], json!({ "line": 2, "character": 22 }));
+    assert_eq!(client.request("textDocument/hover", at(0, 0)), Value::Null);
+
+    let definition = client.request("textDocument/definition", at(3, 2));
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(definition["range"]["start"], json!({ "line": 2, "character": 6 }));
+
+    let symbols = client.request("textDocument/documentSymbol", json!({ "textDocument": { "uri": URI } }));
+    assert_eq!(symbols.as_array().unwrap().len(), 2);
+    assert_eq!(symbols[0]["name"], "x");
+    assert_eq!(symbols[0]["detail"], "i64");
+    assert_eq!(symbols[1]["name"], "y");
+    assert_eq!(symbols[1]["children"][0]["name"], "z");
+
+    let tokens = client.request("textDocument/semanticTokens/full", json!({ "textDocument": { "uri": URI } }));
+    // `{`, then `let` on the next line, relative to it.
+    assert_eq!(tokens["data"].as_array().unwrap()[..10], [0, 0, 1, 4, 0, 1, 2, 3, 0, 0]);
+    client.shutdown();
+}
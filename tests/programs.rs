@@ -0,0 +1,215 @@
+//! Runs each program of `tests/programs` with `lang run` and compares what
+//! it prints and its exit status with what it expects, either:
+//!
+//! - its `// expect: LINE` comments, the lines of stdout in order, with
+//!   `// expect-error: TEXT` for text stderr contains and
+//!   `// expect-status: N` for the exit status, 0 by default; or
+//! - without such comments, the `.out` file next to it, holding the exit
+//!   status, stdout and stderr.
+//!
+//! `// args: ARGS` comments give more arguments to `lang run`. Programs run
+//! from their directory, so that diagnostics show their name only. Each
+//! program also runs with the interpreter, which must print the same as the
+//! JIT.
+//!
+//! `cargo test --test programs -- --bless` writes the `.out` files from
+//! what the programs print now. Other arguments select the programs whose
+//! name contains them. The options of the libtest harness are accepted and
+//! ignored.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// What running a program printed.
+struct Run {
+    status: i32,
+    stdout: String,
+    stderr: String,
+}
+
+impl Run {
+    /// The contents of a `.out` file.
+    fn transcript(&self) -> String {
+        format!("status: {}\n--- stdout\n{}--- stderr\n{}", self.status, self.stdout, self.stderr)
+    }
+}
+
+/// The comments of a program starting with `// expect`, and its arguments.
+#[derive(Default)]
+struct Expectations {
+    stdout: Vec<String>,
+    errors: Vec<String>,
+    status: Option<i32>,
+    args: Vec<String>,
+}
+
+impl Expectations {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut expectations = Expectations::default();
+        for (i, line) in text.lines().enumerate() {
+            let Some(comment) = line.split_once("//").map(|(_, comment)| comment.trim()) else {
+                continue;
+            };
+            if let Some(line) = comment.strip_prefix("expect:") {
+                expectations.stdout.push(line.trim().to_string());
+            } else if let Some(error) = comment.strip_prefix("expect-error:") {
+                expectations.errors.push(error.trim().to_string());
+            } else if let Some(status) = comment.strip_prefix("expect-status:") {
+                let status = status.trim().parse().map_err(|_| format!("line {}: invalid status {:?}", i + 1, status))?;
+                expectations.status = Some(status);
+            } else if let Some(args) = comment.strip_prefix("args:") {
+                expectations.args.extend(args.split_whitespace().map(str::to_string));
+            } else if comment.starts_with("expect") {
+                return Err(format!("line {}: unknown expectation {:?}", i + 1, comment));
+            }
+        }
+        Ok(expectations)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.stdout.is_empty() && self.errors.is_empty() && self.status.is_none()
+    }
+
+    /// What differs from the expectations.
+    fn check(&self, run: &Run) -> Result<(), String> {
+        let stdout = run.stdout.lines().collect::<Vec<_>>();
+        let mut mismatches = Vec::new();
+        if stdout != self.stdout {
+            mismatches.push(format!("expected stdout:\n{}\nfound:\n{}", self.stdout.join("\n"), run.stdout));
+        }
+        for error in &self.errors {
+            if !run.stderr.contains(error.as_str()) {
+                mismatches.push(format!("expected stderr to contain {:?}", error));
+            }
+        }
+        if run.status != self.status.unwrap_or(0) {
+            mismatches.push(format!("expected status {} but found {}", self.status.unwrap_or(0), run.status));
+        }
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(format!("{}\nstderr:\n{}", mismatches.join("\n"), run.stderr)),
+        }
+    }
+}
+
+fn run(dir: &Path, name: &str, args: &[String]) -> Run {
+    let output = common::output(
+        common::lang()
+            .current_dir(dir)
+            .arg("run")
+            .args(args)
+            .arg(name)
+            .env("NO_COLOR", "1")
+            .env_remove("CLICOLOR_FORCE"),
+    );
+    Run {
+        status: output.status.code().unwrap_or(-1),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }
+}
+
+/// Runs a program and checks its output, or writes its `.out` file when
+/// blessing.
+fn check(path: &Path, bless: bool) -> Result<(), String> {
+    let dir = path.parent().expect("programs are in a directory");
+    let name = path.file_name().expect("programs have a name").to_string_lossy();
+    let text = fs::read_to_string(path).map_err(|e| format!("couldn't read the program: {}", e))?;
+    let expectations = Expectations::parse(&text)?;
+    let run = run(dir, &name, &expectations.args);
+    let mut interp_args = expectations.args.clone();
+    interp_args.push("--interp".to_string());
+    let interp = self::run(dir, &name, &interp_args);
+    if interp.transcript() != run.transcript() {
+        return Err(format!("the JIT printed:\n{}\nbut the interpreter:\n{}", run.transcript(), interp.transcript()));
+    }
+    if !expectations.is_empty() {
+        return expectations.check(&run);
+    }
+
+    let out = path.with_extension("out");
+    if bless {
+        return fs::write(&out, run.transcript()).map_err(|e| format!("couldn't write {}: {}", out.display(), e));
+    }
+    match fs::read_to_string(&out) {
+        Ok(expected) if expected == run.transcript() => Ok(()),
+        Ok(expected) => Err(format!("expected:\n{}\nfound:\n{}", expected, run.transcript())),
+        Err(_) => Err(format!("no '// expect:' comments nor {}; run with --bless to write it", out.display())),
+    }
+}
+
+/// Options of the libtest harness which `cargo test` may pass on.
+const FLAGS: &[&str] = &[
+    "-q", "--quiet", "--nocapture", "--no-capture", "--show-output", "--ignored", "--include-ignored", "--exact", "--test",
+];
+/// Options of the libtest harness which take a value.
+const OPTIONS: &[&str] = &["--test-threads", "--skip", "--format", "--color", "--logfile", "--shuffle-seed", "-Z"];
+
+/// Whether to bless, and the filters.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<(bool, Vec<String>), String> {
+    let mut args = args.into_iter();
+    let (mut bless, mut filters) = (false, Vec::new());
+    while let Some(arg) = args.next() {
+        match arg.split_once('=').map_or(arg.as_str(), |(option, _)| option) {
+            "--bless" => bless = true,
+            // The value follows, unless it is written `--option=value`.
+            option if OPTIONS.contains(&option) => {
+                if option == arg {
+                    args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+                }
+            },
+            option if FLAGS.contains(&option) => (),
+            option if option.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ => filters.push(arg.clone()),
+        }
+    }
+    Ok((bless, filters))
+}
+
+fn main() -> ExitCode {
+    let (bless, filters) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs");
+    let mut paths = fs::read_dir(&dir)
+        .expect("tests/programs is readable")
+        .map(|entry| entry.expect("tests/programs is readable").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "x"))
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            filters.is_empty() || filters.iter().any(|filter| name.contains(filter.as_str()))
+        })
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    println!("\nrunning {} programs", paths.len());
+    let mut failures = Vec::new();
+    for path in &paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let result = check(path, bless);
+        println!("program {} ... {}", name, if result.is_ok() { "ok" } else { "FAILED" });
+        if let Err(message) = result {
+            failures.push((name, message));
+        }
+    }
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, message) in &failures {
+            println!("\n---- {} ----\n{}", name, message);
+        }
+    }
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed\n", status, paths.len() - failures.len(), failures.len());
+    match failures.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,10 @@
+{
+    let x := 2;
+    let y: f64 = {
+        let x := 3;
+        x * x
+    };
+    return x + y;
+    0
+}
+// expect: output: 11
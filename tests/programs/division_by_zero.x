@@ -0,0 +1,7 @@
+#![allow(unconditional_fault)]
+{
+    let zero := 0 as i64;
+    1 as i64 / zero
+}
+// expect-status: 3
+// expect-error: runtime error: division by zero at division_by_zero.x:4:14
@@ -0,0 +1,7 @@
+{
+    let seven := 7 as i64;
+    let minus := 0 as i64 - seven;
+    // Division rounds toward zero.
+    (minus / (2 as i64)) * (10 as i64) + seven
+}
+// expect: output: -23
@@ -0,0 +1,5 @@
+{
+    let x := 1 as i64;
+    let y: f64 = x;
+    x + y
+}
@@ -0,0 +1,5 @@
+// Items are joined by the operators starting the next line.
+30 + 12
+
++ 6
+// expect: output: 48
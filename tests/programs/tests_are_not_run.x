@@ -0,0 +1,6 @@
+test "never run" {
+    assert(1 == 2);
+}
+
+40 + 2
+// expect: output: 42
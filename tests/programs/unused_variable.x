@@ -0,0 +1,5 @@
+{
+    let unused := 1;
+    let answer := 42;
+    answer
+}
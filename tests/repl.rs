@@ -0,0 +1,50 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+fn repl(history: &PathBuf, stdin: &str) -> Output {
+    common::output_with_stdin(common::lang().arg("repl").arg("--history").arg(history), stdin)
+}
+
+fn history_path(name: &str) -> PathBuf {
+    let path = common::temp_path(&format!("repl-{}", name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_entries() {
+    let history = history_path("entries");
+    let output = repl(&history, "let x := 3\nx * 2\nlet y := {\n  let z := x + 1;\n  z * z\n}\ny\n1 as i64 / (x - 3) as i64\ny\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "6\n16\n16\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("division by zero at <entry 5>:1:10"), "{}", stderr);
+    let _ = std::fs::remove_file(history);
+}
+
+#[test]
+fn test_errors() {
+    let history = history_path("errors");
+    let output = repl(&history, "let x := 1; y\nx\n2\n:quit\n3\n");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Cannot find variable 'y' in this scope"), "{}", stderr);
+    assert!(stderr.contains("<entry 2>:1:1"), "{}", stderr);
+    let _ = std::fs::remove_file(history);
+}
+
+#[test]
+fn test_history() {
+    let history = history_path("history");
+    repl(&history, "let x := {\n  2\n}\nx + 1\n");
+    let output = repl(&history, ":history\n!1\n!!\nx * 10\n!9\n");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "   1  let x := {\n  2\n}\n   2  x + 1\nlet x := {\n  2\n}\nlet x := {\n  2\n}\n20\n",
+    );
+    assert!(String::from_utf8(output.stderr).unwrap().contains("no entry !9 in the history"));
+    let _ = std::fs::remove_file(history);
+}
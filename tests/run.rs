@@ -0,0 +1,126 @@
+mod common;
+
+use std::process::Output;
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    common::output_with_stdin(common::lang().arg("run").args(args), stdin)
+}
+
+fn eval(code: &str) -> Output {
+    run(&["-e", code], "")
+}
+
+#[test]
+fn test_empty_input() {
+    for code in ["", "   \n\n  "] {
+        let output = run(&["-"], code);
+        assert!(output.status.success(), "{:?}", output);
+        assert!(output.stdout.is_empty() && output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_malformed_input() {
+    for code in ["}", "{", "(", ")", "+", "{ let }", "let x := 1", "1 +; 2", "#![", "#[x] 1", "{ ;;; }", "§"] {
+        let output = eval(code);
+        assert_eq!(output.status.code(), Some(1), "{:?}: {:?}", code, output);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("error"), "{:?}: {}", code, stderr);
+        assert!(!stderr.contains("internal compiler error"), "{:?}: {}", code, stderr);
+    }
+}
+
+#[test]
+fn test_unknown_variable() {
+    let output = eval("{ let x := 1; x + y }");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Cannot find variable 'y' in this scope"));
+}
+
+#[test]
+fn test_output() {
+    let output = eval("{ let x := 4; x * x } + 2");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "output: 18\n");
+}
+
+#[test]
+fn test_integers() {
+    let output = eval("{ let x: i64 = 7 as i64; x / (2 as i64) + x }");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "output: 10\n");
+}
+
+#[test]
+fn test_unconditional_faults() {
+    let output = eval("{\n  let zero := 0 as i64;\n  1 as i64 / zero\n}");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("error: this operation will fail at runtime: division by zero"), "{}", stderr);
+    assert!(stderr.contains("<eval>:3:12"), "{}", stderr);
+}
+
+#[test]
+fn test_runtime_traps() {
+    let traps = [
+        ("{\n  let zero := 0 as i64;\n  1 as i64 / zero\n}", "division by zero at <eval>:3:12"),
+        ("{ let min := (0 - 9223372036854775808) as i64; min / (0 - 1) as i64 }", "attempt to divide with overflow at <eval>:1:52"),
+        ("{ let big := 99999999999999999999; big as i64 }", "out-of-range conversion to i64 at <eval>:1:40"),
+        ("{ let zero := 0; (zero / zero) as i64 }", "out-of-range conversion to i64 at <eval>:1:32"),
+        ("(0 / (1 - 1)) as i64", "out-of-range conversion to i64 at <eval>:1:15"),
+    ];
+    for (code, message) in traps {
+        let output = run(&["-A", "unconditional_fault", "-e", code], "");
+        assert_eq!(output.status.code(), Some(3), "{:?}: {:?}", code, output);
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), format!("runtime error: {}\n", message), "{:?}", code);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn test_no_runtime_checks() {
+    let output = run(&["--no-runtime-checks", "-A", "unconditional_fault", "-e", "{ let big := 99999999999999999999; big as i64 } as f64 * 0"], "");
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn test_dump() {
+    let output = run(&["--dump", "sexp", "-e", "{ let x := 2; 1 - x - 3 as i64 }"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "(block (let x 2) (return (- 1 (- x (as 3 i64)))))\n");
+    let output = run(&["--dump", "tokens", "-e", "1+x"], "");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1:1     0..1      Number(1)\n1:2     1..2      Operator(+)\n1:3     2..3      Symbol(x)\n");
+    let output = run(&["--dump", "ast", "-e", "1 +; 2"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Item 5..6\n  Literal 2\n");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("error[E0001]"));
+}
+
+/// The interpreter, which runs programs as written, gives the same output,
+/// errors and exit status as the JIT, which runs them folded.
+#[test]
+fn test_interpreter() {
+    let programs = [
+        "{ let x := 4; x * x } + 2",
+        "{ let x: i64 = 7 as i64; x / (2 as i64) + x }",
+        "1 - 2 - 3 / 4 * 5",
+        "{ let x := 1; { let x := x + 1; return x * 3; x } } 7 as i64",
+        "{ let y := 0 / 0; y } + (1 / 0) * 0",
+        "(4611686018427387904 as i64 * (2 as i64)) as f64",
+        "{\n  let zero := 0 as i64;\n  1 as i64 / zero\n}",
+        "{ let min := (0 - 9223372036854775808) as i64; min / (0 - 1) as i64 }",
+        "{ let big := 99999999999999999999; big as i64 }",
+        "{ let zero := 0; (zero / zero) as i64 }",
+        "{ let x := 1; x + y }",
+        "1 +",
+    ];
+    for code in programs {
+        // Faults are reported at compile time, unless they are allowed to
+        // fail at runtime.
+        for args in [&["-e", code][..], &["-A", "unconditional_fault", "-e", code]] {
+            let (jit, interp) = (run(args, ""), run(&[&["--interp"], args].concat(), ""));
+            assert_eq!(interp.status.code(), jit.status.code(), "{:?}: {:?}", code, interp);
+            assert_eq!(String::from_utf8(interp.stdout).unwrap(), String::from_utf8(jit.stdout).unwrap(), "{:?}", code);
+            assert_eq!(String::from_utf8(interp.stderr).unwrap(), String::from_utf8(jit.stderr).unwrap(), "{:?}", code);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::write;
+
+fn test(path: &PathBuf, args: &[&str]) -> Output {
+    common::output(common::lang().arg("test").arg(path).args(args))
+}
+
+const TESTS: &str = "\
+1 / 0
+
+test \"arithmetic\" {
+    let x := 2 as i64;
+    assert(x * x == 4 as i64);
+    assert(1 / 3 < 34 / 100);
+}
+
+test \"rounding\" {
+    assert(1 / 10 + 2 / 10 == 3 / 10);
+}
+
+test \"division\" {
+    let zero := 0 as i64;
+    #[allow(unconditional_fault)]
+    1 as i64 / zero
+}
+";
+
+#[test]
+fn test_run_tests() {
+    let path = write("test_run.x", TESTS);
+    let output = test(&path, &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        format!(
+            "running 3 tests\n\
+             test arithmetic ... ok\n\
+             test rounding ... FAILED\n\
+             test division ... FAILED\n\
+             \n\
+             failures:\n    \
+                 rounding: assertion failed: 1 / 10 + 2 / 10 == 3 / 10 at {0}:10:12\n    \
+                 division: division by zero at {0}:16:14\n\
+             \n\
+             test result: FAILED. 1 passed; 2 failed; 0 filtered out\n",
+            path.display(),
+        ),
+    );
+}
+
+#[test]
+fn test_filter() {
+    let path = write("test_filter.x", TESTS);
+    let output = test(&path, &["arith"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "running 1 test\ntest arithmetic ... ok\n\ntest result: ok. 1 passed; 0 failed; 2 filtered out\n");
+}
+
+#[test]
+fn test_compile_errors() {
+    let path = write("test_errors.x", "test \"types\" { assert(1 as i64 == 1) }");
+    let output = test(&path, &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Cannot compare i64 and f64"), "{}", stderr);
+}